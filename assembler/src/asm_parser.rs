@@ -65,8 +65,7 @@ pub fn parse_program(src: &str) -> Result<Prog, Box<dyn Error>> {
             let opcode = tokens[0].to_lowercase();
 
             // Very minimal parser for operands
-            let operands: Vec<Operand> = if tokens.
-                () > 1 {
+            let operands: Vec<Operand> = if tokens.len() > 1 {
                 tokens[1]
                     .split(',')
                     .map(|tok| parse_operand(tok.trim()))
@@ -129,7 +128,7 @@ fn parse_operand(tok: &str) -> Result<Operand, Box<dyn Error>> {
         Ok(Operand::Reg(parse_register(tok)?))
     } else {
         // Assume it's a label
-        Ok(Operand::Imm(Imm::Lbl(tok.to_string())))
+        Ok(Operand::Imm(Imm::Lbl(tok.into())))
     }
 }
 
@@ -1,6 +1,7 @@
 //! Core assembler structs and helpers (i.e human-readable asm -> byte level representation)
 
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use x86::*;
 
 pub mod asm_parser;
@@ -45,7 +46,7 @@ pub fn sbytes_of_int64(i: i64) -> Vec<SByte> {
         .collect() // collects all 8 bytes into vec<Sbytes>
 }
 
-pub fn int64_of_sbytes(bs: &Vec<SByte>) -> i64 {
+pub fn int64_of_sbytes(bs: &[SByte]) -> i64 {
     bs.iter().rev().fold(0i64, |acc, b| match b {
         SByte::Byte(c) => (acc << 8) | (*c as u8 as i64), // shifted acc becomes last 2 digits, and c is the highest ones
         _ => 0i64,                                        // start with acc = 0
@@ -54,7 +55,7 @@ pub fn int64_of_sbytes(bs: &Vec<SByte>) -> i64 {
 
 pub fn sbytes_of_string(s: &str) -> Vec<SByte> {
     s.chars()
-        .map(|c| SByte::Byte(c))
+        .map(SByte::Byte)
         .chain(std::iter::once(SByte::Byte('\x00')))
         .collect()
 }
@@ -87,8 +88,8 @@ pub fn sbytes_of_ins(ins: &Ins) -> Result<Vec<SByte>, String> {
 pub fn sbytes_of_data(d: &Data) -> Result<Vec<SByte>, String> {
     match d {
         Data::Quad(Imm::Lbl(_)) => Err("sbytes_of_data: tried to serialize a label".to_string()),
-        Data::Asciz(s) => Ok(sbytes_of_string(&s)),
-        Data::Quad(Imm::Lit(i)) => Ok(sbytes_of_int64(i.clone())),
+        Data::Asciz(s) => Ok(sbytes_of_string(s)),
+        Data::Quad(Imm::Lit(i)) => Ok(sbytes_of_int64(*i)),
     }
 }
 
@@ -121,19 +122,18 @@ pub struct Exec {
 }
 
 /// Resolve a symbol (label) to its address
-pub fn resolve_sym(lbl: &str, map: &HashMap<String, i64>) -> Result<i64, UndefinedSym> {
-    map.get(lbl)
-        .copied()
-        .ok_or_else(|| UndefinedSym(lbl.to_string()))
+pub fn resolve_sym(lbl: Lbl, map: &HashMap<Lbl, i64>) -> Result<i64, UndefinedSym> {
+    map.get(&lbl).copied().ok_or(UndefinedSym(lbl))
 }
 
 /// Helper: add a symbol to the symmap, checking for redefinition
-pub fn add_sym(map: &mut HashMap<String, i64>, lbl: &str, addr: i64) -> Result<(), RedefinedSym> {
-    if map.contains_key(lbl) {
-        Err(RedefinedSym(lbl.to_string()))
-    } else {
-        map.insert(lbl.to_string(), addr);
-        Ok(())
+pub fn add_sym(map: &mut HashMap<Lbl, i64>, lbl: Lbl, addr: i64) -> Result<(), RedefinedSym> {
+    match map.entry(lbl) {
+        Entry::Occupied(_) => Err(RedefinedSym(lbl)),
+        Entry::Vacant(e) => {
+            e.insert(addr);
+            Ok(())
+        }
     }
 }
 
@@ -154,18 +154,18 @@ pub fn data_block_size(data_list: &[Data]) -> i64 {
 }
 
 /// Helper: resolve an immediate (Lbl -> Lit with address)
-pub fn resolve_imm(map: &HashMap<String, i64>, imm: &Imm) -> Result<Imm, UndefinedSym> {
+pub fn resolve_imm(map: &HashMap<Lbl, i64>, imm: &Imm) -> Result<Imm, UndefinedSym> {
     match imm {
         Imm::Lit(addr) => Ok(Imm::Lit(*addr)),
         Imm::Lbl(lbl) => {
-            let addr = resolve_sym(lbl, map)?;
+            let addr = resolve_sym(*lbl, map)?;
             Ok(Imm::Lit(addr))
         }
     }
 }
 
 /// Helper: resolve an operand (recursively resolves labels)
-pub fn resolve_operand(map: &HashMap<String, i64>, op: &Operand) -> Result<Operand, UndefinedSym> {
+pub fn resolve_operand(map: &HashMap<Lbl, i64>, op: &Operand) -> Result<Operand, UndefinedSym> {
     match op {
         Operand::Imm(i) => Ok(Operand::Imm(resolve_imm(map, i)?)),
         Operand::Reg(r) => Ok(Operand::Reg(*r)),
@@ -66,7 +66,7 @@ pub fn filter_sections(prog: &Prog) -> (Vec<Elem>, Vec<Elem>) {
 pub fn build_symbol_table(
     text_elems: &[Elem],
     data_elems: &[Elem],
-) -> Result<(HashMap<String, i64>, i64, i64, i64), RedefinedSym> {
+) -> Result<(HashMap<Lbl, i64>, i64, i64, i64), RedefinedSym> {
     let text_pos = 0x400_000i64;
     let mut sym_map = HashMap::new();
     let mut offset = 0i64;
@@ -76,7 +76,7 @@ pub fn build_symbol_table(
         match &elem.asm {
             Asm::Text(ins_list) => {
                 let addr = text_pos + offset;
-                add_sym(&mut sym_map, &elem.lbl, addr)?;
+                add_sym(&mut sym_map, elem.lbl, addr)?;
                 offset += ins_block_size(ins_list);
             }
             Asm::Data(_) => {} // not supposed to be called
@@ -92,7 +92,7 @@ pub fn build_symbol_table(
         match &elem.asm {
             Asm::Data(data_list) => {
                 let addr = data_pos + data_offset;
-                add_sym(&mut sym_map, &elem.lbl, addr)?;
+                add_sym(&mut sym_map, elem.lbl, addr)?;
                 data_offset += data_block_size(data_list);
             }
             Asm::Text(_) => {} // not supposed to be called
@@ -104,7 +104,7 @@ pub fn build_symbol_table(
 }
 
 /// 3 - resolve_labels for ins
-fn resolve_ins_labels(map: &HashMap<String, i64>, ins: &Ins) -> Result<Ins, UndefinedSym> {
+fn resolve_ins_labels(map: &HashMap<Lbl, i64>, ins: &Ins) -> Result<Ins, UndefinedSym> {
     let resolved_operands: Result<Vec<Operand>, UndefinedSym> = ins
         .operands
         .iter()
@@ -118,7 +118,7 @@ fn resolve_ins_labels(map: &HashMap<String, i64>, ins: &Ins) -> Result<Ins, Unde
 }
 
 /// 3.5 - resolve_labels for data (handles quad with labels)
-fn resolve_data_labels(map: &HashMap<String, i64>, data: &Data) -> Result<Data, UndefinedSym> {
+fn resolve_data_labels(map: &HashMap<Lbl, i64>, data: &Data) -> Result<Data, UndefinedSym> {
     match data {
         Data::Asciz(s) => Ok(Data::Asciz(s.clone())),
         Data::Quad(imm) => Ok(Data::Quad(resolve_imm(map, imm)?)),
@@ -129,7 +129,7 @@ fn resolve_data_labels(map: &HashMap<String, i64>, data: &Data) -> Result<Data,
 pub fn assemble(prog: &Prog) -> Result<Exec, Box<dyn std::error::Error>> {
     let (ts, ds) = filter_sections(prog);
     let (map, _text_size, data_pos, _data_size) = build_symbol_table(&ts, &ds)?;
-    let entry = resolve_sym("main", &map)?;
+    let entry = resolve_sym("main".into(), &map)?;
 
     let text_pos = 0x400_000i64;
     let mut text_seg = Vec::new();
@@ -317,8 +317,8 @@ mod tests {
 
         assert_eq!(text_size, 16); // 2 instructions * 8 bytes
         assert_eq!(data_size, 0);
-        assert!(sym_map.contains_key("main"));
-        assert_eq!(*sym_map.get("main").unwrap(), 0x400_000);
+        assert!(sym_map.contains_key(&"main".into()));
+        assert_eq!(*sym_map.get(&"main".into()).unwrap(), 0x400_000);
 
         // Test full assembly
         let exec = assemble(&prog);
@@ -375,9 +375,9 @@ mod tests {
 
         assert_eq!(text_size, 16); // 2 instructions * 8 bytes
         assert_eq!(data_size, 11); // "Hi\0" (3 bytes) + quad (8 bytes)
-        assert!(sym_map.contains_key("main"));
-        assert!(sym_map.contains_key("hello"));
-        assert!(sym_map.contains_key("num"));
+        assert!(sym_map.contains_key(&"main".into()));
+        assert!(sym_map.contains_key(&"hello".into()));
+        assert!(sym_map.contains_key(&"num".into()));
 
         // Test full assembly
         let exec = assemble(&prog);
@@ -401,7 +401,7 @@ mod tests {
             "loop",
             vec![Ins {
                 opcode: Opcode::Jmp,
-                operands: vec![Operand::Imm(Imm::Lbl("loop".to_string()))],
+                operands: vec![Operand::Imm(Imm::Lbl("loop".into()))],
             }],
         );
 
@@ -430,7 +430,7 @@ mod tests {
             "main",
             vec![Ins {
                 opcode: Opcode::Jmp,
-                operands: vec![Operand::Imm(Imm::Lbl("undefined_label".to_string()))],
+                operands: vec![Operand::Imm(Imm::Lbl("undefined_label".into()))],
             }],
         );
 
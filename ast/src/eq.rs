@@ -0,0 +1,236 @@
+// Equality up to source locations
+//
+// The derived `==` on the tree compares everything, spans and node ids
+// included. `eq_ignoring_spans` compares only what was written: `int[]` at
+// two places in a program, or a parsed tree and the same tree built by hand
+// with dummy spans, are equal under it.
+
+use crate::*;
+use common::Spanned;
+
+pub trait EqIgnoringSpans {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool;
+}
+
+// no locations inside: plain equality
+macro_rules! by_eq {
+    ($($t:ty),*) => {
+        $(impl EqIgnoringSpans for $t {
+            fn eq_ignoring_spans(&self, other: &Self) -> bool {
+                self == other
+            }
+        })*
+    };
+}
+
+by_eq!(bool, i64, String, IdTy, UnOp, BinOp);
+
+impl<T: EqIgnoringSpans> EqIgnoringSpans for Spanned<T> {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool {
+        self.node.eq_ignoring_spans(&other.node)
+    }
+}
+
+impl<T: EqIgnoringSpans> EqIgnoringSpans for Node<T> {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool {
+        self.elt.eq_ignoring_spans(&other.elt)
+    }
+}
+
+impl<T: EqIgnoringSpans> EqIgnoringSpans for Box<T> {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool {
+        (**self).eq_ignoring_spans(other)
+    }
+}
+
+impl<T: EqIgnoringSpans> EqIgnoringSpans for Option<T> {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Some(a), Some(b)) => a.eq_ignoring_spans(b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl<T: EqIgnoringSpans> EqIgnoringSpans for Vec<T> {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().zip(other).all(|(a, b)| a.eq_ignoring_spans(b))
+    }
+}
+
+// --- types ---
+
+impl EqIgnoringSpans for Ty {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Ty::TBool, Ty::TBool) | (Ty::TInt, Ty::TInt) => true,
+            (Ty::TRef(r1), Ty::TRef(r2)) | (Ty::TNullRef(r1), Ty::TNullRef(r2)) => {
+                r1.eq_ignoring_spans(r2)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl EqIgnoringSpans for RefTy {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool {
+        match (self, other) {
+            (RefTy::RString, RefTy::RString) => true,
+            (RefTy::RArray(t1), RefTy::RArray(t2)) => t1.eq_ignoring_spans(t2),
+            (RefTy::RFun(args1, ret1), RefTy::RFun(args2, ret2)) => {
+                args1.eq_ignoring_spans(args2) && ret1.eq_ignoring_spans(ret2)
+            }
+            (RefTy::RStruct(id1), RefTy::RStruct(id2)) => id1 == id2,
+            _ => false,
+        }
+    }
+}
+
+impl EqIgnoringSpans for RetTy {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool {
+        match (self, other) {
+            (RetTy::RetVoid, RetTy::RetVoid) => true,
+            (RetTy::RetVal(t1), RetTy::RetVal(t2)) => t1.eq_ignoring_spans(t2),
+            _ => false,
+        }
+    }
+}
+
+// --- expressions ---
+
+impl EqIgnoringSpans for Exp {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool {
+        use Exp::*;
+        match (self, other) {
+            (CNull(r1), CNull(r2)) => r1.eq_ignoring_spans(r2),
+            (CBool(b1), CBool(b2)) => b1 == b2,
+            (CInt(i1), CInt(i2)) => i1 == i2,
+            (CStr(s1), CStr(s2)) => s1 == s2,
+            (CArr(t1, es1), CArr(t2, es2)) => {
+                t1.eq_ignoring_spans(t2) && es1.eq_ignoring_spans(es2)
+            }
+            (NewArr(t1, n1), NewArr(t2, n2)) => {
+                t1.eq_ignoring_spans(t2) && n1.eq_ignoring_spans(n2)
+            }
+            (CStruct(id1, fs1), CStruct(id2, fs2)) => id1 == id2 && fs1.eq_ignoring_spans(fs2),
+            (Id(x1), Id(x2)) => x1 == x2,
+            (Index(a1, i1), Index(a2, i2)) => a1.eq_ignoring_spans(a2) && i1.eq_ignoring_spans(i2),
+            (Length(a1), Length(a2)) => a1.eq_ignoring_spans(a2),
+            (Proj(e1, f1), Proj(e2, f2)) => e1.eq_ignoring_spans(e2) && f1 == f2,
+            (Call(f1, as1), Call(f2, as2)) => {
+                f1.eq_ignoring_spans(f2) && as1.eq_ignoring_spans(as2)
+            }
+            (Bop(b1, l1, r1), Bop(b2, l2, r2)) => {
+                b1 == b2 && l1.eq_ignoring_spans(l2) && r1.eq_ignoring_spans(r2)
+            }
+            (Uop(u1, e1), Uop(u2, e2)) => u1 == u2 && e1.eq_ignoring_spans(e2),
+            (Cond(c1, t1, e1), Cond(c2, t2, e2)) => {
+                c1.eq_ignoring_spans(c2) && t1.eq_ignoring_spans(t2) && e1.eq_ignoring_spans(e2)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl EqIgnoringSpans for CField {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool {
+        self.cf_id == other.cf_id && self.cf_node.eq_ignoring_spans(&other.cf_node)
+    }
+}
+
+// --- statements ---
+
+impl EqIgnoringSpans for VDecl {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool {
+        self.vd_id == other.vd_id
+            && self.vd_ty.eq_ignoring_spans(&other.vd_ty)
+            && self.vd_node.eq_ignoring_spans(&other.vd_node)
+    }
+}
+
+impl EqIgnoringSpans for Stmt {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool {
+        use Stmt::*;
+        match (self, other) {
+            (Assn(l1, r1), Assn(l2, r2)) => l1.eq_ignoring_spans(l2) && r1.eq_ignoring_spans(r2),
+            (OpAssn(l1, b1, r1), OpAssn(l2, b2, r2)) => {
+                b1 == b2 && l1.eq_ignoring_spans(l2) && r1.eq_ignoring_spans(r2)
+            }
+            (Incr(e1), Incr(e2)) | (Decr(e1), Decr(e2)) => e1.eq_ignoring_spans(e2),
+            (Decl(vd1), Decl(vd2)) => vd1.eq_ignoring_spans(vd2),
+            (Ret(e1), Ret(e2)) => e1.eq_ignoring_spans(e2),
+            (SCall(f1, as1), SCall(f2, as2)) => {
+                f1.eq_ignoring_spans(f2) && as1.eq_ignoring_spans(as2)
+            }
+            (If(c1, t1, e1), If(c2, t2, e2)) => {
+                c1.eq_ignoring_spans(c2) && t1.eq_ignoring_spans(t2) && e1.eq_ignoring_spans(e2)
+            }
+            (Cast(r1, x1, v1, t1, e1), Cast(r2, x2, v2, t2, e2)) => {
+                r1.eq_ignoring_spans(r2)
+                    && x1 == x2
+                    && v1.eq_ignoring_spans(v2)
+                    && t1.eq_ignoring_spans(t2)
+                    && e1.eq_ignoring_spans(e2)
+            }
+            (For(vds1, g1, s1, b1), For(vds2, g2, s2, b2)) => {
+                vds1.eq_ignoring_spans(vds2)
+                    && g1.eq_ignoring_spans(g2)
+                    && s1.eq_ignoring_spans(s2)
+                    && b1.eq_ignoring_spans(b2)
+            }
+            (While(c1, b1), While(c2, b2)) => c1.eq_ignoring_spans(c2) && b1.eq_ignoring_spans(b2),
+            (ForEach(x1, a1, b1), ForEach(x2, a2, b2)) => {
+                x1 == x2 && a1.eq_ignoring_spans(a2) && b1.eq_ignoring_spans(b2)
+            }
+            (Break, Break) | (Continue, Continue) => true,
+            _ => false,
+        }
+    }
+}
+
+// --- declarations ---
+
+impl EqIgnoringSpans for GDecl {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool {
+        self.name == other.name && self.init.eq_ignoring_spans(&other.init)
+    }
+}
+
+impl EqIgnoringSpans for Arg {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool {
+        self.id == other.id && self.ty.eq_ignoring_spans(&other.ty)
+    }
+}
+
+impl EqIgnoringSpans for FDecl {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool {
+        self.fname == other.fname
+            && self.fret_ty.eq_ignoring_spans(&other.fret_ty)
+            && self.args.eq_ignoring_spans(&other.args)
+            && self.body.eq_ignoring_spans(&other.body)
+    }
+}
+
+impl EqIgnoringSpans for Field {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool {
+        self.field_name == other.field_name && self.field_type.eq_ignoring_spans(&other.field_type)
+    }
+}
+
+impl EqIgnoringSpans for TDecl {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool {
+        self.td_id == other.td_id && self.td_node.eq_ignoring_spans(&other.td_node)
+    }
+}
+
+impl EqIgnoringSpans for Decl {
+    fn eq_ignoring_spans(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Decl::GVDecl(g1), Decl::GVDecl(g2)) => g1.eq_ignoring_spans(g2),
+            (Decl::GFDecl(f1), Decl::GFDecl(f2)) => f1.eq_ignoring_spans(f2),
+            (Decl::GTDecl(t1), Decl::GTDecl(t2)) => t1.eq_ignoring_spans(t2),
+            _ => false,
+        }
+    }
+}
@@ -1,14 +1,26 @@
 #! Abstract Syntax Tree for Oat
 
-use common;
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Node<T> {
     pub elt: T,
     pub loc: common::Span,
+    pub id: NodeId,
+}
+
+// Identity of a node, for annotating it from a later phase (see `NodeMap`)
+//
+// The parser numbers the nodes of one parse 0, 1, 2, .. in the order it
+// finishes them, so the same source always gets the same ids. Nodes made
+// after parsing (desugaring, tests) use `NodeId::DUMMY` and are never looked
+// up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub u32);
+
+impl NodeId {
+    pub const DUMMY: NodeId = NodeId(u32::MAX);
 }
 
-pub type IdTy = String;
+pub type IdTy = common::Symbol;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Ty {
@@ -18,6 +30,20 @@ pub enum Ty {
     TNullRef(SRefTy),
 }
 
+impl Ty {
+    // initial value of an uninitialized `T x;`, for the types that have one:
+    //   int → 0, bool → false, R? → R null
+    // non-null references have no default and must be initialized
+    pub fn default_exp(&self) -> Option<Exp> {
+        match self {
+            Ty::TInt => Some(Exp::CInt(0)),
+            Ty::TBool => Some(Exp::CBool(false)),
+            Ty::TNullRef(r) => Some(Exp::CNull(r.clone())),
+            Ty::TRef(_) => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum RefTy {
     RString,
@@ -32,18 +58,20 @@ pub enum RetTy {
     RetVal(Box<STy>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum UnOp {
     Neg,
     LogNot,
     BitNot,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum BinOp {
     Add,
     Sub,
     Mul,
+    Div, // truncates toward zero
+    Mod, // takes the sign of the dividend
     Eq,
     Neq,
     Lt,
@@ -59,7 +87,26 @@ pub enum BinOp {
     Sar,
 }
 
-#[derive(Debug, Clone)]
+impl BinOp {
+    // binding strength, higher binds tighter; all binary operators are left-associative
+    //   [|]  <  [&]  <  |  <  &  <  == !=  <  < <= > >=  <  << >> >>>  <  + -  <  * / %
+    pub fn prec(&self) -> u8 {
+        use BinOp::*;
+        match self {
+            IOr => 20,
+            IAnd => 30,
+            Or => 40,
+            And => 50,
+            Eq | Neq => 60,
+            Lt | Lte | Gt | Gte => 70,
+            Shl | Shr | Sar => 80,
+            Add | Sub => 90,
+            Mul | Div | Mod => 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Exp {
     CNull(SRefTy),
     CBool(bool),
@@ -67,35 +114,48 @@ pub enum Exp {
     CStr(String),
     CArr(Ty, Vec<Node<SExp>>),
     NewArr(Ty, Box<Node<SExp>>),
+    CStruct(IdTy, Vec<CField>),
     Id(IdTy),
     Index(Box<Node<SExp>>, Box<Node<SExp>>),
+    Length(Box<Node<SExp>>),
+    Proj(Box<Node<SExp>>, IdTy),
     Call(Box<Node<SExp>>, Vec<Node<SExp>>),
     Bop(BinOp, Box<Node<SExp>>, Box<Node<SExp>>),
     Uop(UnOp, Box<Node<SExp>>),
+    // guard ? e1 : e2, binds looser than every binary operator and nests to the right
+    Cond(Box<Node<SExp>>, Box<Node<SExp>>, Box<Node<SExp>>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct CField {
     pub cf_id: IdTy,
     pub cf_node: Node<SExp>,
 }
 
 // VDecl example:
-//   int x = 5; where vd_id=IntTy, vd_node=Some<Node<CInt(5)>>
-//   int x;     where vd_id=IntTy, vd_node=None
-#[derive(Debug, Clone)]
+//   var x = 5; where vd_id="x", vd_ty=None,      vd_node=Some(CInt(5))
+//   int x = 5; where vd_id="x", vd_ty=Some(int), vd_node=Some(CInt(5))
+//   int x;     where vd_id="x", vd_ty=Some(int), vd_node=None (x starts at int's default)
+#[derive(Debug, Clone, PartialEq)]
 pub struct VDecl {
     pub vd_id: IdTy,
+    pub vd_ty: Option<STy>,
     pub vd_node: Option<Node<SExp>>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Stmt {
     Assn(Node<SExp>, Node<SExp>),
+    // lhs op= e, with lhs evaluated once: x += 1, a[i] <<= 2, p.ok &= b
+    OpAssn(Node<SExp>, BinOp, Node<SExp>),
+    Incr(Node<SExp>), // lhs++
+    Decr(Node<SExp>), // lhs--
     Decl(VDecl),
     Ret(Option<Node<SExp>>),
     SCall(Node<SExp>, Vec<Node<SExp>>),
     If(Node<SExp>, Vec<Node<SStmt>>, Vec<Node<SStmt>>),
+    // if? (r x = e) { .. } else { .. }: x is e as a non-null r, in the then-branch only
+    Cast(SRefTy, IdTy, Node<SExp>, Vec<Node<SStmt>>, Vec<Node<SStmt>>),
     For(
         Vec<VDecl>,
         Option<Node<SExp>>,
@@ -103,23 +163,27 @@ pub enum Stmt {
         Vec<Node<SStmt>>,
     ),
     While(Node<SExp>, Vec<Node<SStmt>>),
+    // for (x in e) { .. }: x is each element of the array e in turn
+    ForEach(IdTy, Node<SExp>, Vec<Node<SStmt>>),
+    Break,    // leave the innermost loop
+    Continue, // next iteration of the innermost loop (runs a for loop's step)
 }
 
 pub type Block = Vec<Node<SStmt>>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct GDecl {
     pub name: IdTy,
     pub init: Node<SExp>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Arg {
     pub ty: STy,
     pub id: IdTy,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct FDecl {
     pub fret_ty: RetTy,
     pub fname: IdTy,
@@ -127,19 +191,19 @@ pub struct FDecl {
     pub body: Block,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Field {
     pub field_name: IdTy,
     pub field_type: Ty,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TDecl {
     pub td_id: IdTy,
     pub td_node: Vec<Field>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Decl {
     GVDecl(Node<GDecl>),
     GFDecl(Node<FDecl>),
@@ -154,3 +218,23 @@ pub type SRetTy = common::Spanned<RetTy>;
 pub type SRefTy = common::Spanned<RefTy>;
 pub type SExp = common::Spanned<Exp>;
 pub type SStmt = common::Spanned<Stmt>;
+
+// Pretty-printer back to Oat source (Display impls for the tree)
+pub mod printer;
+
+pub use printer::print_prog;
+
+// Equality up to spans and node ids (`==` compares them too)
+pub mod eq;
+
+pub use eq::EqIgnoringSpans;
+
+// NodeMap side tables: per-node results of a phase, keyed by NodeId
+pub mod table;
+
+pub use table::NodeMap;
+
+// Visitor / VisitorMut traversals with walk_* helpers for every node kind
+pub mod visit;
+
+pub use visit::{Visitor, VisitorMut};
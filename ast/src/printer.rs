@@ -0,0 +1,398 @@
+// Pretty-printer: AST → Oat source
+//
+// The output parses back to the same tree (up to spans) with as few
+// parentheses as the precedence table in `BinOp::prec` allows (and function
+// types parenthesized under `[]` or `?`, where they would take the suffix as
+// part of their return type). A negative `CInt` has no source form of its own
+// and prints as `-n`, which parses back as `Neg(n)`.
+
+use crate::*;
+use std::fmt;
+
+const INDENT: &str = "    ";
+
+// binding strength of an expression, on the same scale as `BinOp::prec`
+const PREC_COND: u8 = 10;
+const PREC_UNARY: u8 = 110;
+const PREC_POSTFIX: u8 = 120;
+
+fn exp_prec(e: &Exp) -> u8 {
+    match e {
+        Exp::Bop(op, _, _) => op.prec(),
+        Exp::Cond(_, _, _) => PREC_COND,
+        Exp::Uop(_, _) => PREC_UNARY,
+        Exp::CInt(i) if *i < 0 => PREC_UNARY,
+        _ => PREC_POSTFIX,
+    }
+}
+
+/// write `e`, parenthesized if it binds looser than `min_prec`
+fn write_sub(f: &mut fmt::Formatter<'_>, e: &Node<SExp>, min_prec: u8) -> fmt::Result {
+    if exp_prec(&e.elt.node) < min_prec {
+        write!(f, "({})", e.elt.node)
+    } else {
+        write!(f, "{}", e.elt.node)
+    }
+}
+
+/// write a type followed by `[]` or `[n]`: a function type in parentheses,
+/// `((int) -> int)[]`, since `(int) -> int[]` returns an array
+fn write_elt_ty(f: &mut fmt::Formatter<'_>, t: &Ty) -> fmt::Result {
+    match t {
+        Ty::TRef(r) if matches!(r.node, RefTy::RFun(..)) => write!(f, "({})", t),
+        _ => write!(f, "{}", t),
+    }
+}
+
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter<'_>, items: &[T]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+fn write_exps(f: &mut fmt::Formatter<'_>, es: &[Node<SExp>]) -> fmt::Result {
+    for (i, e) in es.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", e.elt.node)?;
+    }
+    Ok(())
+}
+
+fn write_str(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            '\r' => write!(f, "\\r")?,
+            '\0' => write!(f, "\\0")?,
+            '\\' => write!(f, "\\\\")?,
+            '"' => write!(f, "\\\"")?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+fn write_indent(f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+    for _ in 0..depth {
+        write!(f, "{}", INDENT)?;
+    }
+    Ok(())
+}
+
+fn write_block(f: &mut fmt::Formatter<'_>, b: &Block, depth: usize) -> fmt::Result {
+    if b.is_empty() {
+        return write!(f, "{{ }}");
+    }
+    writeln!(f, "{{")?;
+    for s in b {
+        write_indent(f, depth + 1)?;
+        write_stmt(f, &s.elt.node, depth + 1)?;
+        writeln!(f)?;
+    }
+    write_indent(f, depth)?;
+    write!(f, "}}")
+}
+
+/// statements that may appear as a `for` step: no trailing `;`
+fn write_simple_stmt(f: &mut fmt::Formatter<'_>, s: &Stmt, depth: usize) -> fmt::Result {
+    match s {
+        Stmt::Assn(lhs, rhs) => write!(f, "{} = {}", lhs.elt.node, rhs.elt.node),
+        Stmt::OpAssn(lhs, op, rhs) => write!(f, "{} {}= {}", lhs.elt.node, op, rhs.elt.node),
+        Stmt::Incr(lhs) => write!(f, "{}++", lhs.elt.node),
+        Stmt::Decr(lhs) => write!(f, "{}--", lhs.elt.node),
+        Stmt::Decl(vd) => write!(f, "{}", vd),
+        Stmt::SCall(fun, args) => {
+            write_sub(f, fun, PREC_POSTFIX)?;
+            write!(f, "(")?;
+            write_exps(f, args)?;
+            write!(f, ")")
+        }
+        s => write_stmt(f, s, depth),
+    }
+}
+
+/// else branch of an `if` or `if?`: a lone nested conditional prints as `else if`
+fn write_else(f: &mut fmt::Formatter<'_>, else_block: &Block, depth: usize) -> fmt::Result {
+    match else_block.as_slice() {
+        [] => Ok(()),
+        [s] if matches!(s.elt.node, Stmt::If(..) | Stmt::Cast(..)) => {
+            write!(f, " else ")?;
+            write_stmt(f, &s.elt.node, depth)
+        }
+        _ => {
+            write!(f, " else ")?;
+            write_block(f, else_block, depth)
+        }
+    }
+}
+
+/// a statement, starting at the current column, with nested lines at `depth`
+fn write_stmt(f: &mut fmt::Formatter<'_>, s: &Stmt, depth: usize) -> fmt::Result {
+    match s {
+        Stmt::Assn(_, _)
+        | Stmt::OpAssn(_, _, _)
+        | Stmt::Incr(_)
+        | Stmt::Decr(_)
+        | Stmt::Decl(_)
+        | Stmt::SCall(_, _) => {
+            write_simple_stmt(f, s, depth)?;
+            write!(f, ";")
+        }
+        Stmt::Ret(None) => write!(f, "return;"),
+        Stmt::Ret(Some(e)) => write!(f, "return {};", e.elt.node),
+        Stmt::If(guard, then_block, else_block) => {
+            write!(f, "if ({}) ", guard.elt.node)?;
+            write_block(f, then_block, depth)?;
+            write_else(f, else_block, depth)
+        }
+        Stmt::Cast(r, x, e, then_block, else_block) => {
+            write!(f, "if? ({} {} = {}) ", r.node, x, e.elt.node)?;
+            write_block(f, then_block, depth)?;
+            write_else(f, else_block, depth)
+        }
+        Stmt::For(vdecls, guard, step, body) => {
+            write!(f, "for (")?;
+            write_list(f, vdecls)?;
+            write!(f, "; ")?;
+            if let Some(g) = guard {
+                write!(f, "{}", g.elt.node)?;
+            }
+            write!(f, "; ")?;
+            if let Some(s) = step {
+                write_simple_stmt(f, &s.elt.node, depth)?;
+            }
+            write!(f, ") ")?;
+            write_block(f, body, depth)
+        }
+        Stmt::While(guard, body) => {
+            write!(f, "while ({}) ", guard.elt.node)?;
+            write_block(f, body, depth)
+        }
+        Stmt::ForEach(x, arr, body) => {
+            write!(f, "for ({} in {}) ", x, arr.elt.node)?;
+            write_block(f, body, depth)
+        }
+        Stmt::Break => write!(f, "break;"),
+        Stmt::Continue => write!(f, "continue;"),
+    }
+}
+
+impl fmt::Display for UnOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            UnOp::Neg => "-",
+            UnOp::LogNot => "!",
+            UnOp::BitNot => "~",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Mod => "%",
+            BinOp::Eq => "==",
+            BinOp::Neq => "!=",
+            BinOp::Lt => "<",
+            BinOp::Lte => "<=",
+            BinOp::Gt => ">",
+            BinOp::Gte => ">=",
+            BinOp::And => "&",
+            BinOp::Or => "|",
+            BinOp::IAnd => "[&]",
+            BinOp::IOr => "[|]",
+            BinOp::Shl => "<<",
+            BinOp::Shr => ">>",
+            BinOp::Sar => ">>>",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::TBool => write!(f, "bool"),
+            Ty::TInt => write!(f, "int"),
+            Ty::TRef(r) => write!(f, "{}", r.node),
+            Ty::TNullRef(r) if matches!(r.node, RefTy::RFun(..)) => write!(f, "({})?", r.node),
+            Ty::TNullRef(r) => write!(f, "{}?", r.node),
+        }
+    }
+}
+
+impl fmt::Display for RefTy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RefTy::RString => write!(f, "string"),
+            RefTy::RStruct(id) => write!(f, "{}", id),
+            RefTy::RArray(t) => {
+                write_elt_ty(f, &t.node)?;
+                write!(f, "[]")
+            }
+            RefTy::RFun(args, ret) => {
+                write!(f, "(")?;
+                for (i, a) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", a.node)?;
+                }
+                write!(f, ") -> {}", ret.node)
+            }
+        }
+    }
+}
+
+impl fmt::Display for RetTy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetTy::RetVoid => write!(f, "void"),
+            RetTy::RetVal(t) => write!(f, "{}", t.node),
+        }
+    }
+}
+
+impl fmt::Display for Exp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Exp::CNull(r) => write!(f, "{} null", r.node),
+            Exp::CBool(b) => write!(f, "{}", b),
+            Exp::CInt(i) => write!(f, "{}", i),
+            Exp::CStr(s) => write_str(f, s),
+            Exp::CArr(t, es) => {
+                write!(f, "new ")?;
+                write_elt_ty(f, t)?;
+                write!(f, "[]{{")?;
+                write_exps(f, es)?;
+                write!(f, "}}")
+            }
+            Exp::NewArr(t, size) => {
+                write!(f, "new ")?;
+                write_elt_ty(f, t)?;
+                write!(f, "[{}]", size.elt.node)
+            }
+            Exp::CStruct(id, fields) => {
+                if fields.is_empty() {
+                    return write!(f, "new {} {{ }}", id);
+                }
+                write!(f, "new {} {{ ", id)?;
+                for (i, cf) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{} = {}", cf.cf_id, cf.cf_node.elt.node)?;
+                }
+                write!(f, " }}")
+            }
+            Exp::Id(id) => write!(f, "{}", id),
+            Exp::Index(arr, idx) => {
+                write_sub(f, arr, PREC_POSTFIX)?;
+                write!(f, "[{}]", idx.elt.node)
+            }
+            Exp::Length(e) => write!(f, "length({})", e.elt.node),
+            Exp::Proj(e, field) => {
+                write_sub(f, e, PREC_POSTFIX)?;
+                write!(f, ".{}", field)
+            }
+            Exp::Call(fun, args) => {
+                write_sub(f, fun, PREC_POSTFIX)?;
+                write!(f, "(")?;
+                write_exps(f, args)?;
+                write!(f, ")")
+            }
+            Exp::Bop(op, l, r) => {
+                // left-associative: only the right operand needs parens at equal precedence
+                write_sub(f, l, op.prec())?;
+                write!(f, " {} ", op)?;
+                write_sub(f, r, op.prec() + 1)
+            }
+            Exp::Uop(op, e) => {
+                write!(f, "{}", op)?;
+                // keep `- -x` from printing as `--x`
+                if matches!(e.elt.node, Exp::Uop(UnOp::Neg, _))
+                    || matches!(e.elt.node, Exp::CInt(i) if i < 0)
+                {
+                    write!(f, " ")?;
+                }
+                write_sub(f, e, PREC_UNARY)
+            }
+            Exp::Cond(guard, e1, e2) => {
+                // right-associative: only the guard needs parens at equal precedence
+                write_sub(f, guard, PREC_COND + 1)?;
+                write!(f, " ? {} : ", e1.elt.node)?;
+                write_sub(f, e2, PREC_COND)
+            }
+        }
+    }
+}
+
+impl fmt::Display for VDecl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.vd_ty {
+            Some(t) => write!(f, "{} {}", t.node, self.vd_id)?,
+            None => write!(f, "var {}", self.vd_id)?,
+        }
+        match &self.vd_node {
+            Some(e) => write!(f, " = {}", e.elt.node),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_stmt(f, self, 0)
+    }
+}
+
+impl fmt::Display for Decl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Decl::GVDecl(g) => write!(f, "global {} = {};", g.elt.name, g.elt.init.elt.node),
+            Decl::GTDecl(t) => {
+                if t.elt.td_node.is_empty() {
+                    return write!(f, "struct {} {{ }}", t.elt.td_id);
+                }
+                writeln!(f, "struct {} {{", t.elt.td_id)?;
+                for field in &t.elt.td_node {
+                    writeln!(f, "{}{} {};", INDENT, field.field_type, field.field_name)?;
+                }
+                write!(f, "}}")
+            }
+            Decl::GFDecl(fd) => {
+                let fd = &fd.elt;
+                write!(f, "{} {}(", fd.fret_ty, fd.fname)?;
+                for (i, a) in fd.args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{} {}", a.ty.node, a.id)?;
+                }
+                write!(f, ") ")?;
+                write_block(f, &fd.body, 0)
+            }
+        }
+    }
+}
+
+/// Oat source for a whole program, one blank line between declarations
+pub fn print_prog(prog: &Prog) -> String {
+    let decls: Vec<String> = prog.iter().map(|d| d.to_string()).collect();
+    let mut out = decls.join("\n\n");
+    out.push('\n');
+    out
+}
@@ -0,0 +1,70 @@
+// Side tables
+//
+// A phase that learns something about nodes (the type of an expression, the
+// declaration a name refers to, the value of a constant) records it in a
+// `NodeMap` instead of in the tree, and later phases look it up by the
+// node. The tree itself stays as the parser built it.
+//
+//     let mut tys: NodeMap<Ty> = NodeMap::new();
+//     tys.insert(e, Ty::TInt);
+//     assert_eq!(tys.get(e), Some(&Ty::TInt));
+
+use crate::{Node, NodeId};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeMap<V> {
+    map: HashMap<NodeId, V>,
+}
+
+impl<V> Default for NodeMap<V> {
+    fn default() -> Self {
+        Self {
+            map: HashMap::new(),
+        }
+    }
+}
+
+impl<V> NodeMap<V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// record `v` for `node`, returning what was there before; nodes with
+    /// `NodeId::DUMMY` are not recorded, they would all share one entry
+    pub fn insert<T>(&mut self, node: &Node<T>, v: V) -> Option<V> {
+        self.insert_id(node.id, v)
+    }
+
+    pub fn insert_id(&mut self, id: NodeId, v: V) -> Option<V> {
+        if id == NodeId::DUMMY {
+            return None;
+        }
+        self.map.insert(id, v)
+    }
+
+    pub fn get<T>(&self, node: &Node<T>) -> Option<&V> {
+        self.get_id(node.id)
+    }
+
+    pub fn get_id(&self, id: NodeId) -> Option<&V> {
+        self.map.get(&id)
+    }
+
+    pub fn contains<T>(&self, node: &Node<T>) -> bool {
+        self.map.contains_key(&node.id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// every entry, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &V)> {
+        self.map.iter().map(|(id, v)| (*id, v))
+    }
+}
@@ -0,0 +1,296 @@
+// Traversals over the tree
+//
+// `Visitor` reads the tree, `VisitorMut` may rewrite it in place. Each
+// `visit_*` method defaults to the matching `walk_*` function, which visits
+// the children of the node in source order; override a method to act on
+// that kind of node, and call `walk_*` from it to keep descending.
+//
+// Types are not visited: they are leaves as far as these traits go.
+//
+// Example, counting calls:
+//
+//     struct Calls(usize);
+//     impl Visitor for Calls {
+//         fn visit_exp(&mut self, e: &Node<SExp>) {
+//             if let Exp::Call(..) = e.elt.node {
+//                 self.0 += 1;
+//             }
+//             walk_exp(self, e);
+//         }
+//     }
+
+use crate::*;
+
+pub trait Visitor {
+    fn visit_prog(&mut self, prog: &Prog) {
+        for d in prog {
+            self.visit_decl(d);
+        }
+    }
+
+    fn visit_decl(&mut self, d: &Decl) {
+        walk_decl(self, d)
+    }
+
+    fn visit_block(&mut self, b: &Block) {
+        for s in b {
+            self.visit_stmt(s);
+        }
+    }
+
+    fn visit_stmt(&mut self, s: &Node<SStmt>) {
+        walk_stmt(self, s)
+    }
+
+    fn visit_vdecl(&mut self, vd: &VDecl) {
+        walk_vdecl(self, vd)
+    }
+
+    fn visit_exp(&mut self, e: &Node<SExp>) {
+        walk_exp(self, e)
+    }
+}
+
+pub fn walk_decl<V: Visitor + ?Sized>(v: &mut V, d: &Decl) {
+    match d {
+        Decl::GVDecl(g) => v.visit_exp(&g.elt.init),
+        Decl::GFDecl(f) => v.visit_block(&f.elt.body),
+        Decl::GTDecl(_) => {}
+    }
+}
+
+pub fn walk_vdecl<V: Visitor + ?Sized>(v: &mut V, vd: &VDecl) {
+    if let Some(e) = &vd.vd_node {
+        v.visit_exp(e);
+    }
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(v: &mut V, s: &Node<SStmt>) {
+    match &s.elt.node {
+        Stmt::Assn(lhs, e) | Stmt::OpAssn(lhs, _, e) => {
+            v.visit_exp(lhs);
+            v.visit_exp(e);
+        }
+        Stmt::Incr(lhs) | Stmt::Decr(lhs) => v.visit_exp(lhs),
+        Stmt::Decl(vd) => v.visit_vdecl(vd),
+        Stmt::Ret(e) => {
+            if let Some(e) = e {
+                v.visit_exp(e);
+            }
+        }
+        Stmt::SCall(f, args) => {
+            v.visit_exp(f);
+            for a in args {
+                v.visit_exp(a);
+            }
+        }
+        Stmt::If(guard, then_block, else_block) => {
+            v.visit_exp(guard);
+            v.visit_block(then_block);
+            v.visit_block(else_block);
+        }
+        Stmt::Cast(_, _, e, then_block, else_block) => {
+            v.visit_exp(e);
+            v.visit_block(then_block);
+            v.visit_block(else_block);
+        }
+        Stmt::For(vdecls, guard, step, body) => {
+            for vd in vdecls {
+                v.visit_vdecl(vd);
+            }
+            if let Some(g) = guard {
+                v.visit_exp(g);
+            }
+            if let Some(s) = step {
+                v.visit_stmt(s);
+            }
+            v.visit_block(body);
+        }
+        Stmt::While(guard, body) => {
+            v.visit_exp(guard);
+            v.visit_block(body);
+        }
+        Stmt::ForEach(_, e, body) => {
+            v.visit_exp(e);
+            v.visit_block(body);
+        }
+        Stmt::Break | Stmt::Continue => {}
+    }
+}
+
+pub fn walk_exp<V: Visitor + ?Sized>(v: &mut V, e: &Node<SExp>) {
+    match &e.elt.node {
+        Exp::CNull(_) | Exp::CBool(_) | Exp::CInt(_) | Exp::CStr(_) | Exp::Id(_) => {}
+        Exp::CArr(_, es) => {
+            for e in es {
+                v.visit_exp(e);
+            }
+        }
+        Exp::NewArr(_, len) => v.visit_exp(len),
+        Exp::CStruct(_, fields) => {
+            for f in fields {
+                v.visit_exp(&f.cf_node);
+            }
+        }
+        Exp::Index(arr, idx) => {
+            v.visit_exp(arr);
+            v.visit_exp(idx);
+        }
+        Exp::Length(e) | Exp::Proj(e, _) | Exp::Uop(_, e) => v.visit_exp(e),
+        Exp::Call(f, args) => {
+            v.visit_exp(f);
+            for a in args {
+                v.visit_exp(a);
+            }
+        }
+        Exp::Bop(_, l, r) => {
+            v.visit_exp(l);
+            v.visit_exp(r);
+        }
+        Exp::Cond(guard, e1, e2) => {
+            v.visit_exp(guard);
+            v.visit_exp(e1);
+            v.visit_exp(e2);
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// In place
+// -----------------------------------------------------------------------------
+
+/// like `Visitor`, with mutable access; a method may replace the node it is
+/// given (e.g. desugaring `x++` to `x += 1`) before or after walking it
+pub trait VisitorMut {
+    fn visit_prog(&mut self, prog: &mut Prog) {
+        for d in prog {
+            self.visit_decl(d);
+        }
+    }
+
+    fn visit_decl(&mut self, d: &mut Decl) {
+        walk_decl_mut(self, d)
+    }
+
+    fn visit_block(&mut self, b: &mut Block) {
+        for s in b {
+            self.visit_stmt(s);
+        }
+    }
+
+    fn visit_stmt(&mut self, s: &mut Node<SStmt>) {
+        walk_stmt_mut(self, s)
+    }
+
+    fn visit_vdecl(&mut self, vd: &mut VDecl) {
+        walk_vdecl_mut(self, vd)
+    }
+
+    fn visit_exp(&mut self, e: &mut Node<SExp>) {
+        walk_exp_mut(self, e)
+    }
+}
+
+pub fn walk_decl_mut<V: VisitorMut + ?Sized>(v: &mut V, d: &mut Decl) {
+    match d {
+        Decl::GVDecl(g) => v.visit_exp(&mut g.elt.init),
+        Decl::GFDecl(f) => v.visit_block(&mut f.elt.body),
+        Decl::GTDecl(_) => {}
+    }
+}
+
+pub fn walk_vdecl_mut<V: VisitorMut + ?Sized>(v: &mut V, vd: &mut VDecl) {
+    if let Some(e) = &mut vd.vd_node {
+        v.visit_exp(e);
+    }
+}
+
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(v: &mut V, s: &mut Node<SStmt>) {
+    match &mut s.elt.node {
+        Stmt::Assn(lhs, e) | Stmt::OpAssn(lhs, _, e) => {
+            v.visit_exp(lhs);
+            v.visit_exp(e);
+        }
+        Stmt::Incr(lhs) | Stmt::Decr(lhs) => v.visit_exp(lhs),
+        Stmt::Decl(vd) => v.visit_vdecl(vd),
+        Stmt::Ret(e) => {
+            if let Some(e) = e {
+                v.visit_exp(e);
+            }
+        }
+        Stmt::SCall(f, args) => {
+            v.visit_exp(f);
+            for a in args {
+                v.visit_exp(a);
+            }
+        }
+        Stmt::If(guard, then_block, else_block) => {
+            v.visit_exp(guard);
+            v.visit_block(then_block);
+            v.visit_block(else_block);
+        }
+        Stmt::Cast(_, _, e, then_block, else_block) => {
+            v.visit_exp(e);
+            v.visit_block(then_block);
+            v.visit_block(else_block);
+        }
+        Stmt::For(vdecls, guard, step, body) => {
+            for vd in vdecls {
+                v.visit_vdecl(vd);
+            }
+            if let Some(g) = guard {
+                v.visit_exp(g);
+            }
+            if let Some(s) = step {
+                v.visit_stmt(s);
+            }
+            v.visit_block(body);
+        }
+        Stmt::While(guard, body) => {
+            v.visit_exp(guard);
+            v.visit_block(body);
+        }
+        Stmt::ForEach(_, e, body) => {
+            v.visit_exp(e);
+            v.visit_block(body);
+        }
+        Stmt::Break | Stmt::Continue => {}
+    }
+}
+
+pub fn walk_exp_mut<V: VisitorMut + ?Sized>(v: &mut V, e: &mut Node<SExp>) {
+    match &mut e.elt.node {
+        Exp::CNull(_) | Exp::CBool(_) | Exp::CInt(_) | Exp::CStr(_) | Exp::Id(_) => {}
+        Exp::CArr(_, es) => {
+            for e in es {
+                v.visit_exp(e);
+            }
+        }
+        Exp::NewArr(_, len) => v.visit_exp(len),
+        Exp::CStruct(_, fields) => {
+            for f in fields {
+                v.visit_exp(&mut f.cf_node);
+            }
+        }
+        Exp::Index(arr, idx) => {
+            v.visit_exp(arr);
+            v.visit_exp(idx);
+        }
+        Exp::Length(e) | Exp::Proj(e, _) | Exp::Uop(_, e) => v.visit_exp(e),
+        Exp::Call(f, args) => {
+            v.visit_exp(f);
+            for a in args {
+                v.visit_exp(a);
+            }
+        }
+        Exp::Bop(_, l, r) => {
+            v.visit_exp(l);
+            v.visit_exp(r);
+        }
+        Exp::Cond(guard, e1, e2) => {
+            v.visit_exp(guard);
+            v.visit_exp(e1);
+            v.visit_exp(e2);
+        }
+    }
+}
@@ -3,7 +3,6 @@
 //! translates llvm ir (from llvm crate) into x86 assembly (x86 crate)
 //! output can be fed directly into assembler which produces exec
 
-use llvm;
 use std::collections::HashMap;
 use x86::asm::*;
 use x86::{Cnd, Data, Elem, Imm, Ins, Opcode, Operand, Prog, Reg};
@@ -50,6 +49,12 @@ fn size_ty(tdecls: &HashMap<llvm::Tid, llvm::Ty>, t: &llvm::Ty) -> usize {
     }
 }
 
+/// round a number of stack bytes up so that %rsp stays 16-byte aligned,
+/// as the System V ABI requires at every call
+fn align_stack(nbytes: usize) -> usize {
+    nbytes.div_ceil(16) * 16
+}
+
 /// resolve named types recursively
 fn resolve_ty<'a>(tdecls: &'a HashMap<llvm::Tid, llvm::Ty>, t: &'a llvm::Ty) -> &'a llvm::Ty {
     match t {
@@ -225,6 +230,58 @@ fn compile_insn(ctxt: &Ctxt, uid: &llvm::Uid, insn: &llvm::Insn) -> Vec<Ins> {
     let mut result = Vec::new();
 
     match insn {
+        // x1 in %rdx:%rax, x2 in %rbx; the quotient lands in %rax, the remainder in %rdx
+        //
+        // idivq faults on MIN / -1, so with m = (x2 == -1) in %rcx, the divisor
+        // is x2 + 2m (1 instead of -1) and the quotient is multiplied by 1 - 2m:
+        // x1 / -1 is the wrapped -x1, and x1 % 1 is already 0
+        llvm::Insn::Binop(bop @ (llvm::Bop::SDiv | llvm::Bop::SRem), _ty, x1, x2) => {
+            let ins = |opcode, operands| Ins { opcode, operands };
+            let (rax, rbx, rcx) = (
+                Operand::Reg(Reg::Rax),
+                Operand::Reg(Reg::Rbx),
+                Operand::Reg(Reg::Rcx),
+            );
+            result.push(compile_operand(ctxt, rax.clone(), x1));
+            result.push(compile_operand(ctxt, rbx.clone(), x2));
+
+            // m, through dst (setcc writes a byte)
+            result.push(ins(
+                Opcode::Movq,
+                vec![Operand::Imm(Imm::Lit(0)), dst.clone()],
+            ));
+            result.push(ins(
+                Opcode::Cmpq,
+                vec![Operand::Imm(Imm::Lit(-1)), rbx.clone()],
+            ));
+            result.push(ins(Opcode::Set(Cnd::Eq), vec![dst.clone()]));
+            result.push(ins(Opcode::Movq, vec![dst.clone(), rcx.clone()]));
+            result.push(ins(Opcode::Addq, vec![rcx.clone(), rbx.clone()]));
+            result.push(ins(Opcode::Addq, vec![rcx.clone(), rbx.clone()]));
+
+            result.push(ins(Opcode::Cqto, vec![]));
+            result.push(ins(Opcode::Idivq, vec![rbx]));
+            let res = if matches!(bop, llvm::Bop::SDiv) {
+                result.push(ins(
+                    Opcode::Shlq,
+                    vec![Operand::Imm(Imm::Lit(1)), rcx.clone()],
+                ));
+                result.push(ins(Opcode::Negq, vec![rcx.clone()]));
+                result.push(ins(
+                    Opcode::Addq,
+                    vec![Operand::Imm(Imm::Lit(1)), rcx.clone()],
+                ));
+                result.push(ins(Opcode::Imulq, vec![rcx, rax]));
+                Reg::Rax
+            } else {
+                Reg::Rdx
+            };
+            result.push(Ins {
+                opcode: Opcode::Movq,
+                operands: vec![Operand::Reg(res), dst],
+            });
+        }
+
         llvm::Insn::Binop(bop, _ty, x1, x2) => {
             result.push(compile_operand(ctxt, Operand::Reg(Reg::Rax), x1));
             result.push(compile_operand(ctxt, Operand::Reg(Reg::Rbx), x2));
@@ -256,6 +313,7 @@ fn compile_insn(ctxt: &Ctxt, uid: &llvm::Uid, insn: &llvm::Insn) -> Vec<Ins> {
                 llvm::Bop::And => Opcode::Andq,
                 llvm::Bop::Or => Opcode::Orq,
                 llvm::Bop::Xor => Opcode::Xorq,
+                llvm::Bop::SDiv | llvm::Bop::SRem => unreachable!("division is compiled above"),
             };
             let src = if matches!(bop, llvm::Bop::Shl | llvm::Bop::Lshr | llvm::Bop::Ashr) {
                 Operand::Reg(Reg::Rcx)
@@ -273,7 +331,7 @@ fn compile_insn(ctxt: &Ctxt, uid: &llvm::Uid, insn: &llvm::Insn) -> Vec<Ins> {
         }
 
         llvm::Insn::Alloca(ty) => {
-            let nbytes = size_ty(&ctxt.tdecls, ty);
+            let nbytes = align_stack(size_ty(&ctxt.tdecls, ty));
             result.push(Ins {
                 opcode: Opcode::Subq,
                 operands: vec![
@@ -328,6 +386,15 @@ fn compile_insn(ctxt: &Ctxt, uid: &llvm::Uid, insn: &llvm::Insn) -> Vec<Ins> {
         llvm::Insn::Call(ret_ty, callee, args) => {
             // arg regs: rdi, rsi, rdx, rcx, r8, r9, then stack
             let arg_regs = [Reg::Rdi, Reg::Rsi, Reg::Rdx, Reg::Rcx, Reg::R08, Reg::R09];
+            // pad an odd number of stack arguments to keep %rsp aligned
+            let nstack = args.len().saturating_sub(6);
+            let stack_bytes = align_stack(8 * nstack);
+            if nstack % 2 == 1 {
+                result.push(Ins {
+                    opcode: Opcode::Subq,
+                    operands: vec![Operand::Imm(Imm::Lit(8)), Operand::Reg(Reg::Rsp)],
+                });
+            }
             for (i, (_ty, arg_op)) in args.iter().enumerate() {
                 if i < 6 {
                     result.push(compile_operand(ctxt, Operand::Reg(arg_regs[i]), arg_op));
@@ -341,8 +408,9 @@ fn compile_insn(ctxt: &Ctxt, uid: &llvm::Uid, insn: &llvm::Insn) -> Vec<Ins> {
             }
 
             // call
+            // a uid holds a function pointer; any other Id names a function
             match callee {
-                llvm::Operand::Id(f) => {
+                llvm::Operand::Id(f) if !ctxt.layout.contains_key(f) => {
                     result.push(Ins {
                         opcode: Opcode::Callq,
                         operands: vec![Operand::Imm(Imm::Lbl(mangle(f)))],
@@ -357,6 +425,17 @@ fn compile_insn(ctxt: &Ctxt, uid: &llvm::Uid, insn: &llvm::Insn) -> Vec<Ins> {
                 }
             }
 
+            // pop the stack arguments (and padding)
+            if stack_bytes > 0 {
+                result.push(Ins {
+                    opcode: Opcode::Addq,
+                    operands: vec![
+                        Operand::Imm(Imm::Lit(stack_bytes as i64)),
+                        Operand::Reg(Reg::Rsp),
+                    ],
+                });
+            }
+
             // store return val if non-void
             if !matches!(ret_ty, llvm::Ty::Void) {
                 result.push(Ins {
@@ -389,8 +468,8 @@ fn compile_insn(ctxt: &Ctxt, uid: &llvm::Uid, insn: &llvm::Insn) -> Vec<Ins> {
 // --- terminator compilation ---
 
 /// make label unique to function: fn.label
-fn mk_lbl(fname: &str, lbl: &llvm::Lbl) -> String {
-    format!("{}.{}", fname, lbl)
+fn mk_lbl(fname: &str, lbl: &llvm::Lbl) -> x86::Lbl {
+    format!("{}.{}", fname, lbl).into()
 }
 
 /// compile block terminator → x86 insns
@@ -492,17 +571,17 @@ fn stack_layout(args: &[llvm::Uid], cfg: &llvm::Cfg) -> Layout {
 
     let mut all_uids = args.to_vec();
     for (uid, _) in &entry_blk.insns {
-        all_uids.push(uid.clone());
+        all_uids.push(*uid);
     }
     for (_, blk) in labeled_blks {
         for (uid, _) in &blk.insns {
-            all_uids.push(uid.clone());
+            all_uids.push(*uid);
         }
     }
 
     for (i, uid) in all_uids.iter().enumerate() {
         let offset = -(((i + 1) * 8) as i64);
-        layout.insert(uid.clone(), Operand::Ind3(Imm::Lit(offset), Reg::Rbp));
+        layout.insert(*uid, Operand::Ind3(Imm::Lit(offset), Reg::Rbp));
     }
 
     layout
@@ -517,7 +596,7 @@ pub fn compile_fdecl(
     let fname = mangle(name);
     let tdecls_map: HashMap<_, _> = tdecls.iter().cloned().collect();
     let layout = stack_layout(&fdecl.f_param, &fdecl.f_cfg);
-    let frame_size = layout.len() * 8;
+    let frame_size = align_stack(layout.len() * 8);
 
     let ctxt = Ctxt {
         tdecls: tdecls_map,
@@ -598,6 +677,9 @@ fn compile_gdecl(lbl: &str, gdecl: &llvm::Gdecl) -> Elem {
 
 // --- program compilation ---
 
+/// initializes the globals that are not constants, when the frontend emits it
+const INIT_GLOBALS: &str = "oat_init_globals";
+
 /// compile full llvm prog → x86 prog
 pub fn compile_prog(prog: &llvm::Prog) -> Prog {
     let mut result = Vec::new();
@@ -613,21 +695,53 @@ pub fn compile_prog(prog: &llvm::Prog) -> Prog {
     }
 
     // wrapper for 'program' label → 'main' (ocaml compat)
+    // main is entered with %rsp 8 off alignment (its return address)
     if prog.fdecls.iter().any(|(n, _)| n == "program") {
-        let wrapper = gtext(
-            &mangle("main"),
-            vec![
+        let mut body = vec![Ins {
+            opcode: Opcode::Subq,
+            operands: vec![Operand::Imm(Imm::Lit(8)), Operand::Reg(Reg::Rsp)],
+        }];
+        // globals the frontend could not lay out statically are set first;
+        // program's arguments are saved around the call (two pushes keep %rsp aligned)
+        if prog.fdecls.iter().any(|(n, _)| n == INIT_GLOBALS) {
+            body.extend([
+                Ins {
+                    opcode: Opcode::Pushq,
+                    operands: vec![Operand::Reg(Reg::Rdi)],
+                },
+                Ins {
+                    opcode: Opcode::Pushq,
+                    operands: vec![Operand::Reg(Reg::Rsi)],
+                },
                 Ins {
                     opcode: Opcode::Callq,
-                    operands: vec![Operand::Imm(Imm::Lbl(mangle("program")))],
+                    operands: vec![Operand::Imm(Imm::Lbl(mangle(INIT_GLOBALS)))],
                 },
                 Ins {
-                    opcode: Opcode::Retq,
-                    operands: vec![],
+                    opcode: Opcode::Popq,
+                    operands: vec![Operand::Reg(Reg::Rsi)],
                 },
-            ],
-        );
-        result.push(wrapper);
+                Ins {
+                    opcode: Opcode::Popq,
+                    operands: vec![Operand::Reg(Reg::Rdi)],
+                },
+            ]);
+        }
+        body.extend([
+            Ins {
+                opcode: Opcode::Callq,
+                operands: vec![Operand::Imm(Imm::Lbl(mangle("program")))],
+            },
+            Ins {
+                opcode: Opcode::Addq,
+                operands: vec![Operand::Imm(Imm::Lit(8)), Operand::Reg(Reg::Rsp)],
+            },
+            Ins {
+                opcode: Opcode::Retq,
+                operands: vec![],
+            },
+        ]);
+        result.push(gtext(&mangle("main"), body));
     }
 
     Prog(result)
@@ -636,11 +750,11 @@ pub fn compile_prog(prog: &llvm::Prog) -> Prog {
 // --- platform-specific mangling ---
 
 /// platform-specific label mangling (macos adds '_', linux doesn't)
-fn mangle(s: &str) -> String {
+fn mangle(s: &str) -> x86::Lbl {
     if cfg!(target_os = "macos") {
-        format!("_{}", s)
+        format!("_{}", s).into()
     } else {
-        s.to_string()
+        s.into()
     }
 }
 
@@ -686,4 +800,137 @@ mod tests {
         assert!(matches!(compile_cnd(&llvm::Cnd::Sgt), Cnd::Gt));
         assert!(matches!(compile_cnd(&llvm::Cnd::Sge), Cnd::Ge));
     }
+
+    #[test]
+    fn test_division_uses_rax_rdx() {
+        let ctxt = Ctxt {
+            tdecls: HashMap::new(),
+            layout: HashMap::from([("r".into(), Operand::Ind3(Imm::Lit(-8), Reg::Rbp))]),
+        };
+        let div = |bop: llvm::Bop| {
+            let insn = llvm::Insn::Binop(
+                bop,
+                llvm::Ty::I64,
+                llvm::Operand::Const(7),
+                llvm::Operand::Const(2),
+            );
+            compile_insn(&ctxt, &"r".into(), &insn)
+        };
+
+        // quotient from %rax, remainder from %rdx, both after cqto; idivq %rbx,
+        // with the divisor -1 made 1 first and the quotient's sign fixed after
+        let guard = [
+            "movq", "movq", "movq", "cmpq", "sete", "movq", "addq", "addq",
+        ];
+        for (bop, fixup, res) in [
+            (
+                llvm::Bop::SDiv,
+                &["shlq", "negq", "addq", "imulq"][..],
+                Reg::Rax,
+            ),
+            (llvm::Bop::SRem, &[][..], Reg::Rdx),
+        ] {
+            let ins = div(bop);
+            let ops: Vec<_> = ins.iter().map(|i| i.opcode.to_string()).collect();
+            let expected: Vec<_> = guard
+                .iter()
+                .chain(&["cqto", "idivq"])
+                .chain(fixup)
+                .chain(&["movq"])
+                .copied()
+                .collect();
+            assert_eq!(ops, expected);
+            assert!(matches!(ins[9].operands[..], [Operand::Reg(Reg::Rbx)]));
+            assert!(matches!(ins.last().unwrap().operands[..], [Operand::Reg(r), _] if r == res));
+        }
+    }
+
+    #[test]
+    fn test_stack_stays_aligned() {
+        let ctxt = Ctxt {
+            tdecls: HashMap::new(),
+            layout: HashMap::from([("r".into(), Operand::Ind3(Imm::Lit(-8), Reg::Rbp))]),
+        };
+        let rsp_delta = |ins: &[Ins]| -> i64 {
+            ins.iter()
+                .map(|i| match (&i.opcode, &i.operands[..]) {
+                    (Opcode::Subq, [Operand::Imm(Imm::Lit(n)), Operand::Reg(Reg::Rsp)]) => *n,
+                    (Opcode::Addq, [Operand::Imm(Imm::Lit(n)), Operand::Reg(Reg::Rsp)]) => -n,
+                    (Opcode::Pushq, _) => 8,
+                    _ => 0,
+                })
+                .sum()
+        };
+
+        // a one-word alloca takes a whole 16-byte slot
+        let alloca = compile_insn(&ctxt, &"r".into(), &llvm::Insn::Alloca(llvm::Ty::I64));
+        assert_eq!(rsp_delta(&alloca), 16);
+
+        // 7 arguments: one on the stack plus padding, both popped after
+        let args = vec![(llvm::Ty::I64, llvm::Operand::Const(0)); 7];
+        let call = llvm::Insn::Call(llvm::Ty::I64, llvm::Operand::Gid("f".into()), args);
+        let ins = compile_insn(&ctxt, &"r".into(), &call);
+        let at_call = ins
+            .iter()
+            .position(|i| matches!(i.opcode, Opcode::Callq))
+            .unwrap();
+        assert_eq!(rsp_delta(&ins[..at_call]), 16);
+        assert_eq!(rsp_delta(&ins), 0);
+    }
+
+    #[test]
+    fn test_main_runs_global_initializers_first() {
+        let fdecl = || {
+            let ret = ("ret".into(), llvm::Terminator::Ret(llvm::Ty::Void, None));
+            llvm::Fdecl::new(
+                (vec![], llvm::Ty::Void),
+                vec![],
+                (llvm::Block::new(vec![], ret), vec![]),
+            )
+        };
+        let main_ops = |names: &[&str]| {
+            let mut prog = llvm::Prog::new();
+            prog.fdecls = names.iter().map(|n| ((*n).into(), fdecl())).collect();
+            let Prog(elems) = compile_prog(&prog);
+            let main = elems.iter().find(|e| e.lbl == mangle("main")).unwrap();
+            let x86::Asm::Text(ins) = &main.asm else {
+                panic!("main is not code")
+            };
+            ins.iter().map(|i| i.opcode.to_string()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(main_ops(&["program"]), ["subq", "callq", "addq", "retq"]);
+        // program's arguments survive the call, and %rsp stays aligned
+        assert_eq!(
+            main_ops(&["program", INIT_GLOBALS]),
+            [
+                "subq", "pushq", "pushq", "callq", "popq", "popq", "callq", "addq", "retq"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_call_through_uid() {
+        let ctxt = Ctxt {
+            tdecls: HashMap::new(),
+            layout: HashMap::from([
+                ("fp".into(), Operand::Ind3(Imm::Lit(-8), Reg::Rbp)),
+                ("r".into(), Operand::Ind3(Imm::Lit(-16), Reg::Rbp)),
+            ]),
+        };
+        let call = |callee: &str| {
+            let insn = llvm::Insn::Call(llvm::Ty::I64, llvm::Operand::Id(callee.into()), vec![]);
+            compile_insn(&ctxt, &"r".into(), &insn)
+        };
+
+        // a local holding a function pointer: indirect call
+        let ins = call("fp");
+        assert!(ins.iter().any(|i| matches!(i.opcode, Opcode::Callq)
+            && matches!(i.operands[..], [Operand::Reg(Reg::Rax)])));
+
+        // a function name: direct call
+        let ins = call("f");
+        assert!(ins.iter().any(|i| matches!(i.opcode, Opcode::Callq)
+            && matches!(&i.operands[..], [Operand::Imm(Imm::Lbl(l))] if l.ends_with('f'))));
+    }
 }
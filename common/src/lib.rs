@@ -1,3 +1,8 @@
+// interned names, shared by every stage
+pub mod symbol;
+
+pub use symbol::Symbol;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Span {
     pub start: usize,
@@ -34,7 +39,28 @@ pub enum TypeErrorKind {
     UnknownIdentifier { name: String },
     RedundantIdentifier { name: String },
     NotCallable { ty: String },
-    // we can add more error kinds later
+    // number of arguments in a call
+    ArityMismatch { expected: usize, found: usize },
+    // indexed, measured or iterated over, but not an array
+    NotIndexable { ty: String },
+    // a non-void function can reach its end
+    MissingReturn { name: String },
+    // twice in a struct declaration or a struct literal
+    DuplicateField { struct_name: String, field: String },
+    UnknownField { struct_name: String, field: String },
+    // a struct literal without a value for a field
+    MissingField { struct_name: String, field: String },
+    UnknownStruct { name: String },
+    // assigned to, but not a variable, array element or field
+    InvalidLvalue,
+    // declared twice in one scope
+    Redeclaration { name: String },
+    // a void function called for its value
+    VoidInExpression,
+    // break or continue outside of any loop body
+    NotInLoop { stmt: String },
+    // `T x;` or `new T[n]`, but T has no default value (a non-null reference)
+    NoDefaultValue { ty: String },
 }
 
 #[derive(Debug, Clone)]
@@ -50,6 +76,57 @@ impl TypeError {
     }
 }
 
+// LEX ERRORs
+#[derive(Debug, Clone)]
+pub enum LexErrorKind {
+    UnknownChar { ch: char },
+    UnterminatedString,
+    UnterminatedComment,
+    BadEscape { esc: String },
+    IntOverflow { literal: String },
+    MalformedInt { literal: String },
+    BadCharLiteral,
+}
+
+#[derive(Debug, Clone)]
+pub struct LexError {
+    pub msg: String,
+    pub span: Span,
+    pub kind: LexErrorKind,
+}
+
+impl LexError {
+    pub fn new(msg: String, span: Span, kind: LexErrorKind) -> Self {
+        Self { msg, span, kind }
+    }
+}
+
+// PARSE ERRORs
+#[derive(Debug, Clone)]
+pub enum ParseErrorKind {
+    Unexpected { expected: String, found: String },
+    Lexical(LexErrorKind),
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub msg: String,
+    pub span: Span,
+    pub kind: ParseErrorKind,
+}
+
+impl ParseError {
+    pub fn new(msg: String, span: Span, kind: ParseErrorKind) -> Self {
+        Self { msg, span, kind }
+    }
+}
+
+impl From<LexError> for ParseError {
+    fn from(e: LexError) -> Self {
+        Self::new(e.msg, e.span, ParseErrorKind::Lexical(e.kind))
+    }
+}
+
 // // NAME ERRORS
 // #[derive(Debug, Clone)]
 // pub enum NameErrorKind {
@@ -0,0 +1,166 @@
+// Interned strings
+//
+// A `Symbol` is a 4-byte handle to a string stored once in a global table:
+// copying, comparing and hashing one never touches the characters. Names
+// throughout the compiler (identifiers in `ast`, uids, labels and type names
+// in `llvm` and `x86`) are symbols.
+//
+// Interned strings live for the rest of the program.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{LazyLock, RwLock};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+// both directions: text → symbol for interning, symbol → text for reading
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<&'static str, Symbol>,
+    strs: Vec<&'static str>,
+}
+
+static INTERNER: LazyLock<RwLock<Interner>> = LazyLock::new(Default::default);
+
+impl Symbol {
+    /// the symbol for `s`, the same one every time
+    pub fn intern(s: &str) -> Symbol {
+        if let Some(&sym) = INTERNER.read().unwrap().ids.get(s) {
+            return sym;
+        }
+        let mut interner = INTERNER.write().unwrap();
+        // another thread may have interned it in between
+        if let Some(&sym) = interner.ids.get(s) {
+            return sym;
+        }
+        let s: &'static str = Box::leak(s.into());
+        let sym = Symbol(u32::try_from(interner.strs.len()).expect("too many symbols"));
+        interner.strs.push(s);
+        interner.ids.insert(s, sym);
+        sym
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.read().unwrap().strs[self.0 as usize]
+    }
+}
+
+impl std::ops::Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(s: &str) -> Self {
+        Symbol::intern(s)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(s: String) -> Self {
+        Symbol::intern(&s)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(s: &String) -> Self {
+        Symbol::intern(s)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+// by the text, so sorted symbols come out in alphabetical order
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        if self == other {
+            return std::cmp::Ordering::Equal;
+        }
+        let interner = INTERNER.read().unwrap();
+        interner.strs[self.0 as usize].cmp(interner.strs[other.0 as usize])
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// like the string: error messages built with `{:?}` read the same as before
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_is_idempotent() {
+        let a = Symbol::intern("symbol_test_a");
+        let b = Symbol::from(String::from("symbol_test_a"));
+        assert_eq!(a, b);
+        assert_ne!(a, Symbol::intern("symbol_test_b"));
+        assert_eq!(a.as_str(), "symbol_test_a");
+        assert_eq!(a, "symbol_test_a");
+        assert!(a.starts_with("symbol"));
+    }
+
+    #[test]
+    fn test_intern_from_threads() {
+        // every thread gets the same symbols, and reads them back
+        let intern_all = || {
+            (0..100)
+                .map(|i| Symbol::intern(&format!("symbol_test_{}", i)))
+                .collect::<Vec<_>>()
+        };
+        let syms = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..4).map(|_| s.spawn(intern_all)).collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        for other in &syms[1..] {
+            assert_eq!(other, &syms[0]);
+        }
+        for (i, sym) in syms[0].iter().enumerate() {
+            assert_eq!(sym.as_str(), format!("symbol_test_{}", i));
+        }
+    }
+
+    #[test]
+    fn test_formatting_and_order() {
+        let (z, y) = (
+            Symbol::intern("symbol_test_z"),
+            Symbol::intern("symbol_test_y"),
+        );
+        assert_eq!(format!("{} {:?}", z, z), "symbol_test_z \"symbol_test_z\"");
+        let mut syms = vec![z, y];
+        syms.sort();
+        assert_eq!(syms, [y, z]);
+    }
+}
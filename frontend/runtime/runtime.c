@@ -0,0 +1,45 @@
+/* Oat runtime: the functions compiled programs call (see frontend/src/lib.rs)
+ *
+ * link with the assembled program:  gcc -no-pie prog.s runtime.c
+ */
+
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+/* zeroed heap block of `bytes` bytes (structs) */
+int64_t *oat_malloc(int64_t bytes) {
+  int64_t *p = calloc(1, bytes);
+  if (p == NULL) {
+    fprintf(stderr, "oat: out of memory\n");
+    exit(1);
+  }
+  return p;
+}
+
+/* { i64 len, [len x i64] }, elements zeroed */
+int64_t *oat_alloc_array(int64_t len) {
+  int64_t *arr = oat_malloc(8 * (len + 1));
+  arr[0] = len;
+  return arr;
+}
+
+/* a[i] with i outside [0, len); `start`..`end` is the source span of a[i] */
+void oat_bounds_fail(int64_t idx, int64_t len, int64_t start, int64_t end) {
+  fprintf(stderr, "oat: index %ld out of bounds for length %ld at %ld..%ld\n",
+          (long)idx, (long)len, (long)start, (long)end);
+  exit(1);
+}
+
+/* new T[len] with len < 0; `start`..`end` is the source span of the allocation */
+void oat_negative_length(int64_t len, int64_t start, int64_t end) {
+  fprintf(stderr, "oat: negative array length %ld at %ld..%ld\n",
+          (long)len, (long)start, (long)end);
+  exit(1);
+}
+
+/* a / b or a % b with b == 0; `start`..`end` is the source span of the division */
+void oat_div_by_zero(int64_t start, int64_t end) {
+  fprintf(stderr, "oat: division by zero at %ld..%ld\n", (long)start, (long)end);
+  exit(1);
+}
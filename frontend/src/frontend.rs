@@ -0,0 +1,1377 @@
+// Frontend implementation module
+// Compiles expressions, statements, functions and globals to LLVMlite
+
+use crate::*;
+use common::Span;
+use llvm::{Insn, Operand, Terminator};
+
+// runtime functions every program may call
+const OAT_MALLOC: &str = "oat_malloc"; // i64* oat_malloc(i64 bytes)
+const OAT_ALLOC_ARRAY: &str = "oat_alloc_array"; // i64* oat_alloc_array(i64 len), zeroed
+const OAT_BOUNDS_FAIL: &str = "oat_bounds_fail"; // void oat_bounds_fail(i64 idx, i64 len, i64 start, i64 end), exits
+const OAT_NEGATIVE_LENGTH: &str = "oat_negative_length"; // void oat_negative_length(i64 len, i64 start, i64 end), exits
+const OAT_DIV_BY_ZERO: &str = "oat_div_by_zero"; // void oat_div_by_zero(i64 start, i64 end), exits
+
+// sets the globals whose initializers are not constants, in declaration order;
+// defined only when there are some, and run before `program` (backend's main)
+const OAT_INIT_GLOBALS: &str = "oat_init_globals";
+
+fn ptr(t: llvm::Ty) -> llvm::Ty {
+    llvm::Ty::Ptr(Box::new(t))
+}
+
+fn runtime_fun(args: Vec<llvm::Ty>, ret: llvm::Ty) -> llvm::Ty {
+    llvm::Ty::Fun(args, Box::new(ret))
+}
+
+/// every value is 8 bytes wide in the backend
+fn size_of_fields(n: usize) -> i64 {
+    8 * n as i64
+}
+
+fn cmp_bop(b: &BinOp) -> Result<llvm::Bop, llvm::Cnd> {
+    use BinOp::*;
+    match b {
+        Add => Ok(llvm::Bop::Add),
+        Sub => Ok(llvm::Bop::Sub),
+        Mul => Ok(llvm::Bop::Mul),
+        Div => Ok(llvm::Bop::SDiv),
+        Mod => Ok(llvm::Bop::SRem),
+        And | IAnd => Ok(llvm::Bop::And),
+        Or | IOr => Ok(llvm::Bop::Or),
+        Shl => Ok(llvm::Bop::Shl),
+        Shr => Ok(llvm::Bop::Lshr),
+        Sar => Ok(llvm::Bop::Ashr),
+        Eq => Err(llvm::Cnd::Eq),
+        Neq => Err(llvm::Cnd::Ne),
+        Lt => Err(llvm::Cnd::Slt),
+        Lte => Err(llvm::Cnd::Sle),
+        Gt => Err(llvm::Cnd::Sgt),
+        Gte => Err(llvm::Cnd::Sge),
+    }
+}
+
+// pointee of a pointer type
+fn deref(t: &llvm::Ty) -> &llvm::Ty {
+    match t {
+        llvm::Ty::Ptr(t) => t,
+        t => panic!("frontend: expected a pointer, got {:?}", t),
+    }
+}
+
+// EXPRESSIONS ----------------------------------------------------------
+
+/// compile an expression: its llvm type and the operand holding its value
+fn cmp_exp(c: &mut Ctxt, e: &Node<SExp>) -> (llvm::Ty, Operand) {
+    match &e.elt.node {
+        Exp::CNull(r) => (ptr(cmp_rty(&r.node)), Operand::Null),
+
+        Exp::CBool(b) => (llvm::Ty::I1, Operand::Const(*b as i64)),
+
+        Exp::CInt(i) => (llvm::Ty::I64, Operand::Const(*i)),
+
+        // the characters live in a global; the value is a pointer to the first
+        Exp::CStr(s) => {
+            let gid = c.gensym("str");
+            let arr_ty = llvm::Ty::Array(s.len() + 1, Box::new(llvm::Ty::I8));
+            c.emit(Elt::G(
+                gid,
+                (arr_ty.clone(), llvm::Ginit::GString(s.clone())),
+            ));
+            let op = c.insn(
+                "str",
+                Insn::Bitcast(ptr(arr_ty), Operand::Gid(gid), ptr(llvm::Ty::I8)),
+            );
+            (ptr(llvm::Ty::I8), op)
+        }
+
+        Exp::Id(id) => {
+            let (t, op) = c.lookup(*id).clone();
+            match &t {
+                // functions are values already
+                llvm::Ty::Ptr(inner) if matches!(**inner, llvm::Ty::Fun(_, _)) => (t, op),
+                llvm::Ty::Ptr(inner) => {
+                    let v = c.insn(id, Insn::Load(t.clone(), op));
+                    ((**inner).clone(), v)
+                }
+                _ => panic!("frontend: {} is not a pointer", id),
+            }
+        }
+
+        Exp::CArr(t, es) => {
+            let elt_ty = cmp_ty(t);
+            let (arr_ty, arr) = alloc_array(c, t, Operand::Const(es.len() as i64));
+            for (i, e) in es.iter().enumerate() {
+                let v = cmp_exp_as(c, e, &elt_ty);
+                let slot = c.insn(
+                    "elt",
+                    Insn::Gep(
+                        arr_ty.clone(),
+                        arr.clone(),
+                        vec![
+                            Operand::Const(0),
+                            Operand::Const(1),
+                            Operand::Const(i as i64),
+                        ],
+                    ),
+                );
+                c.insn("store", Insn::Store(elt_ty.clone(), v, slot));
+            }
+            (arr_ty, arr)
+        }
+
+        Exp::NewArr(t, size) => {
+            let (_, n) = cmp_exp(c, size);
+            check_length(c, n.clone(), &e.loc);
+            alloc_array(c, t, n)
+        }
+
+        Exp::CStruct(id, cfields) => {
+            let st_ty = ptr(llvm::Ty::Namedt(*id));
+            let nfields = c.struct_fields(*id).len();
+            let raw = c.insn(
+                "raw",
+                Insn::Call(
+                    ptr(llvm::Ty::I64),
+                    Operand::Gid(OAT_MALLOC.into()),
+                    vec![(llvm::Ty::I64, Operand::Const(size_of_fields(nfields)))],
+                ),
+            );
+            let st = c.insn(
+                "struct",
+                Insn::Bitcast(ptr(llvm::Ty::I64), raw, st_ty.clone()),
+            );
+            for cf in cfields {
+                let (idx, fty) = c.lookup_field(*id, cf.cf_id);
+                let fty = cmp_ty(fty);
+                let v = cmp_exp_as(c, &cf.cf_node, &fty);
+                let slot = c.insn(
+                    &cf.cf_id,
+                    Insn::Gep(
+                        st_ty.clone(),
+                        st.clone(),
+                        vec![Operand::Const(0), Operand::Const(idx as i64)],
+                    ),
+                );
+                c.insn("store", Insn::Store(fty, v, slot));
+            }
+            (st_ty, st)
+        }
+
+        Exp::Length(a) => {
+            let (arr_ty, arr) = cmp_exp(c, a);
+            (llvm::Ty::I64, load_length(c, &arr_ty, arr))
+        }
+
+        // read through the address of an array element or struct field
+        Exp::Index(_, _) | Exp::Proj(_, _) => {
+            let (t, addr) = cmp_lhs(c, e);
+            let v = c.insn("load", Insn::Load(ptr(t.clone()), addr));
+            (t, v)
+        }
+
+        Exp::Call(f, args) => cmp_call(c, e, f, args),
+
+        Exp::Bop(b, l, r) => {
+            let (lt, lop) = cmp_exp(c, l);
+            let (_, rop) = cmp_exp(c, r);
+            let v = cmp_binop(c, b, lt, lop, rop, &e.loc);
+            (cmp_ty(c.type_of(e)), v)
+        }
+
+        //       cbr guard, then, else
+        // then: slot = e1 (as the type of the whole); br merge
+        // else: slot = e2 (likewise); br merge
+        // merge: v = load slot
+        Exp::Cond(guard, e1, e2) => {
+            let t = cmp_ty(c.type_of(e));
+            let (_, g) = cmp_exp(c, guard);
+            let (lthen, lelse, lmerge) = (c.gensym("then"), c.gensym("else"), c.gensym("merge"));
+            let slot_id = c.gensym("cond");
+            let slot = Operand::Id(slot_id);
+            c.emit(Elt::E(slot_id, Insn::Alloca(t.clone())));
+            c.term(Terminator::Cbr(g, lthen, lelse));
+
+            c.label(&lthen);
+            let v1 = cmp_exp_as(c, e1, &t);
+            c.insn("store", Insn::Store(t.clone(), v1, slot.clone()));
+            c.term(Terminator::Br(lmerge));
+
+            c.label(&lelse);
+            let v2 = cmp_exp_as(c, e2, &t);
+            c.insn("store", Insn::Store(t.clone(), v2, slot.clone()));
+            c.term(Terminator::Br(lmerge));
+
+            c.label(&lmerge);
+            let v = c.insn("cond", Insn::Load(ptr(t.clone()), slot));
+            (t, v)
+        }
+
+        Exp::Uop(u, e) => {
+            let (t, op) = cmp_exp(c, e);
+            let insn = match u {
+                UnOp::Neg => Insn::Binop(llvm::Bop::Sub, t.clone(), Operand::Const(0), op),
+                UnOp::LogNot => Insn::Binop(llvm::Bop::Xor, t.clone(), op, Operand::Const(1)),
+                UnOp::BitNot => Insn::Binop(llvm::Bop::Xor, t.clone(), op, Operand::Const(-1)),
+            };
+            let v = c.insn("uop", insn);
+            (t, v)
+        }
+    }
+}
+
+/// `l b r` on operands already computed, `l` of type `lt`; `span` is that of
+/// the whole operation (for the division-by-zero trap)
+fn cmp_binop(
+    c: &mut Ctxt,
+    b: &BinOp,
+    lt: llvm::Ty,
+    lop: Operand,
+    rop: Operand,
+    span: &Span,
+) -> Operand {
+    if matches!(b, BinOp::Div | BinOp::Mod) {
+        check_nonzero(c, rop.clone(), span);
+    }
+    match cmp_bop(b) {
+        Ok(bop) => c.insn("bop", Insn::Binop(bop, lt, lop, rop)),
+        Err(cnd) => c.insn("cmp", Insn::Icmp(cnd, lt, lop, rop)),
+    }
+}
+
+/// compile `e` to a value of type `t`, casting between pointer types where
+/// subtyping lets the static types differ (e.g. a struct where a prefix of it
+/// is expected, or `null` of another type)
+fn cmp_exp_as(c: &mut Ctxt, e: &Node<SExp>, t: &llvm::Ty) -> Operand {
+    let (et, op) = cmp_exp(c, e);
+    if et == *t {
+        op
+    } else {
+        c.insn("cast", Insn::Bitcast(et, op, t.clone()))
+    }
+}
+
+/// a fresh array of `len` (zeroed) elements of type `t`
+fn alloc_array(c: &mut Ctxt, t: &Ty, len: Operand) -> (llvm::Ty, Operand) {
+    let arr_ty = ptr(cmp_arr_ty(t));
+    let raw = c.insn(
+        "raw",
+        Insn::Call(
+            ptr(llvm::Ty::I64),
+            Operand::Gid(OAT_ALLOC_ARRAY.into()),
+            vec![(llvm::Ty::I64, len)],
+        ),
+    );
+    let arr = c.insn(
+        "arr",
+        Insn::Bitcast(ptr(llvm::Ty::I64), raw, arr_ty.clone()),
+    );
+    (arr_ty, arr)
+}
+
+/// the length header of an array
+fn load_length(c: &mut Ctxt, arr_ty: &llvm::Ty, arr: Operand) -> Operand {
+    let len_ptr = c.insn(
+        "len_ptr",
+        Insn::Gep(
+            arr_ty.clone(),
+            arr,
+            vec![Operand::Const(0), Operand::Const(0)],
+        ),
+    );
+    c.insn("len", Insn::Load(ptr(llvm::Ty::I64), len_ptr))
+}
+
+//      bad = idx < 0 | idx >= len
+//      cbr bad, fail, ok
+// fail: oat_bounds_fail(idx, len, span); br ok    (does not return)
+// ok:
+fn check_bounds(c: &mut Ctxt, arr_ty: &llvm::Ty, arr: Operand, idx: Operand, span: &Span) {
+    let len = load_length(c, arr_ty, arr);
+    let neg = c.insn(
+        "neg",
+        Insn::Icmp(
+            llvm::Cnd::Slt,
+            llvm::Ty::I64,
+            idx.clone(),
+            Operand::Const(0),
+        ),
+    );
+    let big = c.insn(
+        "big",
+        Insn::Icmp(llvm::Cnd::Sge, llvm::Ty::I64, idx.clone(), len.clone()),
+    );
+    let bad = c.insn("bad", Insn::Binop(llvm::Bop::Or, llvm::Ty::I1, neg, big));
+
+    let (lfail, lok) = (c.gensym("oob"), c.gensym("inbounds"));
+    c.term(Terminator::Cbr(bad, lfail, lok));
+    c.label(&lfail);
+    c.insn(
+        "fail",
+        Insn::Call(
+            llvm::Ty::Void,
+            Operand::Gid(OAT_BOUNDS_FAIL.into()),
+            vec![
+                (llvm::Ty::I64, idx),
+                (llvm::Ty::I64, len),
+                (llvm::Ty::I64, Operand::Const(span.start as i64)),
+                (llvm::Ty::I64, Operand::Const(span.end as i64)),
+            ],
+        ),
+    );
+    c.term(Terminator::Br(lok));
+    c.label(&lok);
+}
+
+//      bad = len < 0
+//      cbr bad, fail, ok
+// fail: oat_negative_length(len, span); br ok    (does not return)
+// ok:
+fn check_length(c: &mut Ctxt, len: Operand, span: &Span) {
+    let bad = c.insn(
+        "bad",
+        Insn::Icmp(
+            llvm::Cnd::Slt,
+            llvm::Ty::I64,
+            len.clone(),
+            Operand::Const(0),
+        ),
+    );
+    let (lfail, lok) = (c.gensym("neglen"), c.gensym("len"));
+    c.term(Terminator::Cbr(bad, lfail, lok));
+    c.label(&lfail);
+    c.insn(
+        "fail",
+        Insn::Call(
+            llvm::Ty::Void,
+            Operand::Gid(OAT_NEGATIVE_LENGTH.into()),
+            vec![
+                (llvm::Ty::I64, len),
+                (llvm::Ty::I64, Operand::Const(span.start as i64)),
+                (llvm::Ty::I64, Operand::Const(span.end as i64)),
+            ],
+        ),
+    );
+    c.term(Terminator::Br(lok));
+    c.label(&lok);
+}
+
+/// trap unless the divisor `d` of `l / d` or `l % d` is nonzero; `span` is
+/// that of the whole division, passed on to the runtime for the message
+fn check_nonzero(c: &mut Ctxt, d: Operand, span: &Span) {
+    let zero = c.insn(
+        "zero",
+        Insn::Icmp(llvm::Cnd::Eq, llvm::Ty::I64, d, Operand::Const(0)),
+    );
+    let (lfail, lok) = (c.gensym("divzero"), c.gensym("nonzero"));
+    c.term(Terminator::Cbr(zero, lfail, lok));
+    c.label(&lfail);
+    c.insn(
+        "fail",
+        Insn::Call(
+            llvm::Ty::Void,
+            Operand::Gid(OAT_DIV_BY_ZERO.into()),
+            vec![
+                (llvm::Ty::I64, Operand::Const(span.start as i64)),
+                (llvm::Ty::I64, Operand::Const(span.end as i64)),
+            ],
+        ),
+    );
+    c.term(Terminator::Br(lok));
+    c.label(&lok);
+}
+
+/// f(args), at node `call`: the result type (Void for statement calls) and operand
+fn cmp_call<T: std::fmt::Debug>(
+    c: &mut Ctxt,
+    call: &Node<T>,
+    f: &Node<SExp>,
+    args: &[Node<SExp>],
+) -> (llvm::Ty, Operand) {
+    let (arg_tys, ret_ty) = c.call_type(call);
+    let (_, fop) = cmp_exp(c, f);
+    let args = args
+        .iter()
+        .zip(arg_tys)
+        .map(|(a, t)| {
+            let op = cmp_exp_as(c, a, &t);
+            (t, op)
+        })
+        .collect();
+    let v = c.insn("call", Insn::Call(ret_ty.clone(), fop, args));
+    (ret_ty, v)
+}
+
+/// address of an lvalue: the type stored there and a pointer to it
+fn cmp_lhs(c: &mut Ctxt, e: &Node<SExp>) -> (llvm::Ty, Operand) {
+    match &e.elt.node {
+        Exp::Id(id) => {
+            let (t, op) = c.lookup(*id).clone();
+            (deref(&t).clone(), op)
+        }
+
+        // &a[i] = gep a, 0, 1, i   once 0 <= i < length(a)
+        Exp::Index(a, i) => {
+            let (arr_ty, arr) = cmp_exp(c, a);
+            let (_, idx) = cmp_exp(c, i);
+            let elt_ty = cmp_ty(c.type_of(e));
+            check_bounds(c, &arr_ty, arr.clone(), idx.clone(), &e.loc);
+            let addr = c.insn(
+                "index",
+                Insn::Gep(arr_ty, arr, vec![Operand::Const(0), Operand::Const(1), idx]),
+            );
+            (elt_ty, addr)
+        }
+
+        // &s.f = gep s, 0, <position of f>
+        Exp::Proj(s, field) => {
+            let (st_ty, st) = cmp_exp(c, s);
+            let Ty::TRef(r) = c.type_of(s) else {
+                panic!("frontend: projecting from {:?}", c.type_of(s))
+            };
+            let RefTy::RStruct(st_name) = r.node else {
+                panic!("frontend: projecting from {:?}", r.node)
+            };
+            let (idx, fty) = c.lookup_field(st_name, *field);
+            let fty = cmp_ty(fty);
+            let addr = c.insn(
+                field,
+                Insn::Gep(
+                    st_ty,
+                    st,
+                    vec![Operand::Const(0), Operand::Const(idx as i64)],
+                ),
+            );
+            (fty, addr)
+        }
+
+        e => panic!("frontend: not an lvalue: {:?}", e),
+    }
+}
+
+// STATEMENTS -----------------------------------------------------------
+
+/// local declaration: a hoisted stack slot, initialized in place
+fn cmp_vdecl(c: &mut Ctxt, vd: &VDecl, span: &Span) {
+    // the initializer is compiled before `x` is in scope: `var x = x + 1;`
+    let (t, v) = match (&vd.vd_ty, &vd.vd_node) {
+        (None, Some(init)) => cmp_exp(c, init),
+        (Some(t), Some(init)) => {
+            let t = cmp_ty(&t.node);
+            let v = cmp_exp_as(c, init, &t);
+            (t, v)
+        }
+        (Some(t), None) => {
+            let default = t
+                .node
+                .default_exp()
+                .unwrap_or_else(|| panic!("frontend: {} has no default value", vd.vd_id));
+            let default = Node {
+                elt: common::Spanned::new(span.clone(), default),
+                loc: span.clone(),
+                id: NodeId::DUMMY,
+            };
+            let t = cmp_ty(&t.node);
+            let v = cmp_exp_as(c, &default, &t);
+            (t, v)
+        }
+        (None, None) => panic!("frontend: {} has no type", vd.vd_id),
+    };
+
+    new_local(c, vd.vd_id, t, v);
+}
+
+/// bind `x` in the current scope to a fresh slot of type `t` holding `v`
+fn new_local(c: &mut Ctxt, x: IdTy, t: llvm::Ty, v: Operand) -> Operand {
+    let uid = c.gensym(&x);
+    c.emit(Elt::E(uid, Insn::Alloca(t.clone())));
+    let slot = Operand::Id(uid);
+    c.insn("store", Insn::Store(t.clone(), v, slot.clone()));
+    c.add_local(x, (ptr(t), slot.clone()));
+    slot
+}
+
+fn cmp_stmt(c: &mut Ctxt, ret_ty: &llvm::Ty, s: &Node<SStmt>) {
+    match &s.elt.node {
+        Stmt::Assn(lhs, e) => {
+            let (t, addr) = cmp_lhs(c, lhs);
+            let v = cmp_exp_as(c, e, &t);
+            c.insn("store", Insn::Store(t, v, addr));
+        }
+
+        // the address of lhs is computed once, then loaded from and stored to
+        Stmt::OpAssn(lhs, b, e) => {
+            let (t, addr) = cmp_lhs(c, lhs);
+            let old = c.insn("old", Insn::Load(ptr(t.clone()), addr.clone()));
+            let (_, rop) = cmp_exp(c, e);
+            let v = cmp_binop(c, b, t.clone(), old, rop, &s.loc);
+            c.insn("store", Insn::Store(t, v, addr));
+        }
+
+        Stmt::Incr(lhs) | Stmt::Decr(lhs) => {
+            let bop = if matches!(s.elt.node, Stmt::Incr(_)) {
+                llvm::Bop::Add
+            } else {
+                llvm::Bop::Sub
+            };
+            let (t, addr) = cmp_lhs(c, lhs);
+            let old = c.insn("old", Insn::Load(ptr(t.clone()), addr.clone()));
+            let v = c.insn("bop", Insn::Binop(bop, t.clone(), old, Operand::Const(1)));
+            c.insn("store", Insn::Store(t, v, addr));
+        }
+
+        Stmt::Decl(vd) => cmp_vdecl(c, vd, &s.loc),
+
+        Stmt::Ret(None) => c.term(Terminator::Ret(llvm::Ty::Void, None)),
+
+        Stmt::Ret(Some(e)) => {
+            let v = cmp_exp_as(c, e, ret_ty);
+            c.term(Terminator::Ret(ret_ty.clone(), Some(v)));
+        }
+
+        Stmt::SCall(f, args) => {
+            cmp_call(c, s, f, args);
+        }
+
+        //      cbr guard, then, else
+        // then: ...; br merge
+        // else: ...; br merge
+        // merge:
+        Stmt::If(guard, then_block, else_block) => {
+            let (_, g) = cmp_exp(c, guard);
+            let (lthen, lelse, lmerge) = (c.gensym("then"), c.gensym("else"), c.gensym("merge"));
+            c.term(Terminator::Cbr(g, lthen, lelse));
+            c.label(&lthen);
+            cmp_block(c, ret_ty, then_block);
+            c.term(Terminator::Br(lmerge));
+            c.label(&lelse);
+            cmp_block(c, ret_ty, else_block);
+            c.term(Terminator::Br(lmerge));
+            c.label(&lmerge);
+        }
+
+        //      isnull = icmp eq e, null
+        //      cbr isnull, else, then
+        // then: x = e (as r); ...; br merge
+        // else: ...; br merge
+        // merge:
+        Stmt::Cast(r, x, e, then_block, else_block) => {
+            let (et, v) = cmp_exp(c, e);
+            let isnull = c.insn(
+                "isnull",
+                Insn::Icmp(llvm::Cnd::Eq, et.clone(), v.clone(), Operand::Null),
+            );
+            let (lthen, lelse, lmerge) = (c.gensym("then"), c.gensym("else"), c.gensym("merge"));
+            c.term(Terminator::Cbr(isnull, lelse, lthen));
+
+            c.label(&lthen);
+            c.push_scope();
+            let t = ptr(cmp_rty(&r.node));
+            let v = if et == t {
+                v
+            } else {
+                c.insn("cast", Insn::Bitcast(et, v, t.clone()))
+            };
+            new_local(c, *x, t, v);
+            cmp_block(c, ret_ty, then_block);
+            c.pop_scope();
+            c.term(Terminator::Br(lmerge));
+
+            c.label(&lelse);
+            cmp_block(c, ret_ty, else_block);
+            c.term(Terminator::Br(lmerge));
+            c.label(&lmerge);
+        }
+
+        // cond: cbr guard, body, post
+        // body: ...; br cond              (break: br post, continue: br cond)
+        // post:
+        Stmt::While(guard, body) => {
+            let (lcond, lbody, lpost) = (c.gensym("cond"), c.gensym("body"), c.gensym("post"));
+            c.term(Terminator::Br(lcond));
+            c.label(&lcond);
+            let (_, g) = cmp_exp(c, guard);
+            c.term(Terminator::Cbr(g, lbody, lpost));
+            c.label(&lbody);
+            c.push_loop(lpost, lcond);
+            cmp_block(c, ret_ty, body);
+            c.pop_loop();
+            c.term(Terminator::Br(lcond));
+            c.label(&lpost);
+        }
+
+        //       len = length(arr); i = 0; br cond
+        // cond: cbr i < len, body, post
+        // body: x = arr[i] (in range, unchecked); ...; br step
+        // step: i = i + 1; br cond         (break: br post, continue: br step)
+        // post:
+        Stmt::ForEach(x, arr, body) => {
+            let (arr_ty, a) = cmp_exp(c, arr);
+            let Ty::TRef(r) = c.type_of(arr) else {
+                panic!("frontend: iterating over {:?}", c.type_of(arr))
+            };
+            let RefTy::RArray(t) = &r.node else {
+                panic!("frontend: iterating over {:?}", r.node)
+            };
+            let elt_ty = cmp_ty(&t.node);
+            let len = load_length(c, &arr_ty, a.clone());
+            let idx = c.gensym("idx");
+            c.emit(Elt::E(idx, Insn::Alloca(llvm::Ty::I64)));
+            let idx = Operand::Id(idx);
+            c.insn(
+                "store",
+                Insn::Store(llvm::Ty::I64, Operand::Const(0), idx.clone()),
+            );
+
+            let (lcond, lbody, lstep, lpost) = (
+                c.gensym("cond"),
+                c.gensym("body"),
+                c.gensym("step"),
+                c.gensym("post"),
+            );
+            c.term(Terminator::Br(lcond));
+            c.label(&lcond);
+            let i = c.insn("i", Insn::Load(ptr(llvm::Ty::I64), idx.clone()));
+            let more = c.insn(
+                "more",
+                Insn::Icmp(llvm::Cnd::Slt, llvm::Ty::I64, i.clone(), len),
+            );
+            c.term(Terminator::Cbr(more, lbody, lpost));
+
+            c.label(&lbody);
+            c.push_scope();
+            let addr = c.insn(
+                "elt",
+                Insn::Gep(
+                    arr_ty,
+                    a,
+                    vec![Operand::Const(0), Operand::Const(1), i.clone()],
+                ),
+            );
+            let v = c.insn("load", Insn::Load(ptr(elt_ty.clone()), addr));
+            new_local(c, *x, elt_ty, v);
+            c.push_loop(lpost, lstep);
+            cmp_block(c, ret_ty, body);
+            c.pop_loop();
+            c.pop_scope();
+            c.term(Terminator::Br(lstep));
+
+            c.label(&lstep);
+            let next = c.insn(
+                "next",
+                Insn::Binop(llvm::Bop::Add, llvm::Ty::I64, i, Operand::Const(1)),
+            );
+            c.insn("store", Insn::Store(llvm::Ty::I64, next, idx));
+            c.term(Terminator::Br(lcond));
+            c.label(&lpost);
+        }
+
+        //       vdecls; br cond
+        // cond: cbr guard, body, post     (no guard: always true)
+        // body: ...; br step              (break: br post, continue: br step)
+        // step: ...; br cond
+        // post:
+        Stmt::For(vdecls, guard, step, body) => {
+            c.push_scope();
+            for vd in vdecls {
+                cmp_vdecl(c, vd, &s.loc);
+            }
+            let (lcond, lbody, lstep, lpost) = (
+                c.gensym("cond"),
+                c.gensym("body"),
+                c.gensym("step"),
+                c.gensym("post"),
+            );
+            c.term(Terminator::Br(lcond));
+            c.label(&lcond);
+            let g = match guard {
+                Some(guard) => cmp_exp(c, guard).1,
+                None => Operand::Const(1),
+            };
+            c.term(Terminator::Cbr(g, lbody, lpost));
+            c.label(&lbody);
+            c.push_loop(lpost, lstep);
+            cmp_block(c, ret_ty, body);
+            c.pop_loop();
+            c.term(Terminator::Br(lstep));
+            c.label(&lstep);
+            if let Some(step) = step {
+                cmp_stmt(c, ret_ty, step);
+            }
+            c.term(Terminator::Br(lcond));
+            c.label(&lpost);
+            c.pop_scope();
+        }
+
+        // code after the jump is unreachable: `take_cfg` drops it
+        Stmt::Break => {
+            let (brk, _) = *c.innermost_loop();
+            c.term(Terminator::Br(brk));
+        }
+
+        Stmt::Continue => {
+            let (_, cont) = *c.innermost_loop();
+            c.term(Terminator::Br(cont));
+        }
+    }
+}
+
+fn cmp_block(c: &mut Ctxt, ret_ty: &llvm::Ty, b: &Block) {
+    c.push_scope();
+    for s in b {
+        cmp_stmt(c, ret_ty, s);
+    }
+    c.pop_scope();
+}
+
+// FUNCTIONS & GLOBALS --------------------------------------------------
+
+fn cmp_fdecl(c: &mut Ctxt, f: &FDecl) -> (llvm::Fdecl, Vec<(llvm::Gid, llvm::Gdecl)>) {
+    let arg_tys: Vec<STy> = f.args.iter().map(|a| a.ty.clone()).collect();
+    let (arg_lltys, ret_ty) = cmp_fty(&arg_tys, &f.fret_ty);
+
+    // parameters are copied into stack slots so they can be assigned
+    c.push_scope();
+    let mut params = Vec::new();
+    for (arg, t) in f.args.iter().zip(&arg_lltys) {
+        let param = c.gensym(&arg.id);
+        let slot = c.gensym(&arg.id);
+        c.emit(Elt::E(slot, Insn::Alloca(t.clone())));
+        c.insn(
+            "store",
+            Insn::Store(t.clone(), Operand::Id(param), Operand::Id(slot)),
+        );
+        c.add_local(arg.id, (ptr(t.clone()), Operand::Id(slot)));
+        params.push(param);
+    }
+    cmp_block(c, &ret_ty, &f.body);
+    c.pop_scope();
+
+    let (cfg, gdecls) = c.take_cfg(&ret_ty);
+    (llvm::Fdecl::new((arg_lltys, ret_ty), params, cfg), gdecls)
+}
+
+/// whether a global initializer can be laid out statically
+///      ex : -1, "s", new int[]{1, 2}, new P { x = 1 } are constants
+///           n, f(), new int[3] are not
+fn is_constant(e: &Node<SExp>) -> bool {
+    match &e.elt.node {
+        Exp::CNull(_) | Exp::CBool(_) | Exp::CInt(_) | Exp::CStr(_) => true,
+        // a negative literal parses as a negated one
+        Exp::Uop(UnOp::Neg, i) => matches!(i.elt.node, Exp::CInt(_)),
+        Exp::CArr(_, es) => es.iter().all(is_constant),
+        Exp::CStruct(_, cfields) => cfields.iter().all(|cf| is_constant(&cf.cf_node)),
+        _ => false,
+    }
+}
+
+/// the value of a global before its initializer has run: 0, false or null
+fn zero_ginit(t: &llvm::Ty) -> llvm::Ginit {
+    match t {
+        llvm::Ty::Ptr(_) => llvm::Ginit::GNull,
+        _ => llvm::Ginit::GInt(0),
+    }
+}
+
+/// global initializer: must be a constant (the data is laid out statically)
+fn cmp_gexp(
+    c: &mut Ctxt,
+    e: &Node<SExp>,
+    gdecls: &mut Vec<(llvm::Gid, llvm::Gdecl)>,
+) -> (llvm::Ty, llvm::Ginit) {
+    match &e.elt.node {
+        Exp::CNull(r) => (ptr(cmp_rty(&r.node)), llvm::Ginit::GNull),
+        Exp::CBool(b) => (llvm::Ty::I1, llvm::Ginit::GInt(*b as i64)),
+        Exp::CInt(i) => (llvm::Ty::I64, llvm::Ginit::GInt(*i)),
+        // a negative literal parses as a negated one
+        Exp::Uop(UnOp::Neg, e1) => match e1.elt.node {
+            Exp::CInt(i) => (llvm::Ty::I64, llvm::Ginit::GInt(i.wrapping_neg())),
+            _ => panic!(
+                "frontend: global initializer is not a constant: {:?}",
+                e.elt.node
+            ),
+        },
+
+        Exp::CStr(s) => {
+            let gid = c.gensym("str");
+            let arr_ty = llvm::Ty::Array(s.len() + 1, Box::new(llvm::Ty::I8));
+            gdecls.push((gid, (arr_ty.clone(), llvm::Ginit::GString(s.clone()))));
+            let cast = llvm::Ginit::GBitcast(
+                ptr(arr_ty),
+                Box::new(llvm::Ginit::GGid(gid)),
+                ptr(llvm::Ty::I8),
+            );
+            (ptr(llvm::Ty::I8), cast)
+        }
+
+        // { i64 n, [n x t] }, referenced as { i64, [0 x t] }*
+        Exp::CArr(t, es) => {
+            let elt_ty = cmp_ty(t);
+            let elts = es.iter().map(|e| cmp_gexp(c, e, gdecls)).collect();
+            let sized_ty = llvm::Ty::Struct(vec![
+                llvm::Ty::I64,
+                llvm::Ty::Array(es.len(), Box::new(elt_ty.clone())),
+            ]);
+            let init = llvm::Ginit::GStruct(vec![
+                (llvm::Ty::I64, llvm::Ginit::GInt(es.len() as i64)),
+                (
+                    llvm::Ty::Array(es.len(), Box::new(elt_ty)),
+                    llvm::Ginit::GArray(elts),
+                ),
+            ]);
+            let gid = c.gensym("arr");
+            gdecls.push((gid, (sized_ty.clone(), init)));
+            let cast = llvm::Ginit::GBitcast(
+                ptr(sized_ty),
+                Box::new(llvm::Ginit::GGid(gid)),
+                ptr(cmp_arr_ty(t)),
+            );
+            (ptr(cmp_arr_ty(t)), cast)
+        }
+
+        // fields in declaration order, whatever the literal's order
+        Exp::CStruct(id, cfields) => {
+            let fields = c.struct_fields(*id).to_vec();
+            let mut init = Vec::new();
+            for field in &fields {
+                let cf = cfields
+                    .iter()
+                    .find(|cf| cf.cf_id == field.field_name)
+                    .unwrap_or_else(|| panic!("frontend: missing field {}", field.field_name));
+                let (_, g) = cmp_gexp(c, &cf.cf_node, gdecls);
+                init.push((cmp_ty(&field.field_type), g));
+            }
+            let gid = c.gensym(id);
+            let st_ty = llvm::Ty::Namedt(*id);
+            gdecls.push((gid, (st_ty.clone(), llvm::Ginit::GStruct(init))));
+            (ptr(st_ty), llvm::Ginit::GGid(gid))
+        }
+
+        e => panic!("frontend: global initializer is not a constant: {:?}", e),
+    }
+}
+
+// PROGRAM --------------------------------------------------------------
+
+/// compile a typechecked program, given the types the typechecker found
+pub fn compile_prog(prog: &ast::Prog, types: &TypeTable) -> llvm::Prog {
+    let mut c = Ctxt::new(types);
+    let mut out = llvm::Prog::new();
+
+    // structs first: any declaration may mention them
+    for decl in prog {
+        if let Decl::GTDecl(td) = decl {
+            let fields = td
+                .elt
+                .td_node
+                .iter()
+                .map(|f| cmp_ty(&f.field_type))
+                .collect();
+            out.tdecls.push((td.elt.td_id, llvm::Ty::Struct(fields)));
+            c.add_struct(td.elt.td_id, td.elt.td_node.clone());
+        }
+    }
+
+    // function signatures, so calls may come before definitions
+    for decl in prog {
+        if let Decl::GFDecl(f) = decl {
+            let arg_tys: Vec<STy> = f.elt.args.iter().map(|a| a.ty.clone()).collect();
+            let (args, ret) = cmp_fty(&arg_tys, &f.elt.fret_ty);
+            let fun_ty = ptr(llvm::Ty::Fun(args, Box::new(ret)));
+            c.add_global(f.elt.fname, (fun_ty, Operand::Gid(f.elt.fname)));
+        }
+    }
+
+    // constant initializers are laid out statically; the others start out
+    // zero, and OAT_INIT_GLOBALS stores their values, in declaration order
+    let mut inits = false;
+    for decl in prog {
+        if let Decl::GVDecl(g) = decl {
+            let init = &g.elt.init;
+            let t = if is_constant(init) {
+                let mut gdecls = Vec::new();
+                let (t, ginit) = cmp_gexp(&mut c, init, &mut gdecls);
+                out.gdecls.append(&mut gdecls);
+                out.gdecls.push((g.elt.name, (t.clone(), ginit)));
+                t
+            } else {
+                // the initializer sees only the globals declared before it
+                let t = cmp_ty(c.type_of(init));
+                out.gdecls.push((g.elt.name, (t.clone(), zero_ginit(&t))));
+                let v = cmp_exp_as(&mut c, init, &t);
+                c.insn("store", Insn::Store(t.clone(), v, Operand::Gid(g.elt.name)));
+                inits = true;
+                t
+            };
+            c.add_global(g.elt.name, (ptr(t), Operand::Gid(g.elt.name)));
+        }
+    }
+    if inits {
+        let (cfg, mut gdecls) = c.take_cfg(&llvm::Ty::Void);
+        out.gdecls.append(&mut gdecls);
+        let fty = (vec![], llvm::Ty::Void);
+        out.fdecls
+            .push((OAT_INIT_GLOBALS.into(), llvm::Fdecl::new(fty, vec![], cfg)));
+    }
+
+    for decl in prog {
+        if let Decl::GFDecl(f) = decl {
+            let (fdecl, mut gdecls) = cmp_fdecl(&mut c, &f.elt);
+            out.gdecls.append(&mut gdecls);
+            out.fdecls.push((f.elt.fname, fdecl));
+        }
+    }
+
+    out.edecls = vec![
+        (
+            OAT_MALLOC.into(),
+            runtime_fun(vec![llvm::Ty::I64], ptr(llvm::Ty::I64)),
+        ),
+        (
+            OAT_ALLOC_ARRAY.into(),
+            runtime_fun(vec![llvm::Ty::I64], ptr(llvm::Ty::I64)),
+        ),
+        (
+            OAT_BOUNDS_FAIL.into(),
+            runtime_fun(vec![llvm::Ty::I64; 4], llvm::Ty::Void),
+        ),
+        (
+            OAT_NEGATIVE_LENGTH.into(),
+            runtime_fun(vec![llvm::Ty::I64; 3], llvm::Ty::Void),
+        ),
+        (
+            OAT_DIV_BY_ZERO.into(),
+            runtime_fun(vec![llvm::Ty::I64; 2], llvm::Ty::Void),
+        ),
+    ];
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(src: &str) -> llvm::Prog {
+        let (prog, errs) = parser::parse_prog(src);
+        assert!(errs.is_empty(), "{:?}", errs);
+        let types = typechecker::typecheck_prog(&prog).expect("typecheck failed");
+        compile_prog(&prog, &types)
+    }
+
+    fn fdecl<'a>(p: &'a llvm::Prog, name: &str) -> &'a llvm::Fdecl {
+        &p.fdecls.iter().find(|(n, _)| n == name).unwrap().1
+    }
+
+    fn insns(f: &llvm::Fdecl) -> Vec<&Insn> {
+        let (entry, blocks) = &f.f_cfg;
+        std::iter::once(entry)
+            .chain(blocks.iter().map(|(_, b)| b))
+            .flat_map(|b| b.insns.iter().map(|(_, i)| i))
+            .collect()
+    }
+
+    #[test]
+    fn test_struct_tdecls() {
+        let p = compile("struct P { int x; bool b; string s; }");
+        assert_eq!(
+            p.tdecls,
+            vec![(
+                "P".into(),
+                llvm::Ty::Struct(vec![llvm::Ty::I64, llvm::Ty::I1, ptr(llvm::Ty::I8)])
+            )]
+        );
+    }
+
+    #[test]
+    fn test_struct_literal_and_projection() {
+        let p = compile(
+            "struct P { int x; int y; }
+            int f() {
+                var p = new P { y = 2; x = 1 };
+                p.y = 5;
+                return p.x;
+            }",
+        );
+        let f = fdecl(&p, "f");
+        let is = insns(f);
+
+        // one allocation for the struct, one gep per field written or read
+        let mallocs = is
+            .iter()
+            .filter(|i| matches!(i, Insn::Call(_, Operand::Gid(g), _) if g == OAT_MALLOC))
+            .count();
+        assert_eq!(mallocs, 1);
+
+        let field_geps: Vec<i64> = is
+            .iter()
+            .filter_map(|i| match i {
+                Insn::Gep(llvm::Ty::Ptr(t), _, path) => {
+                    assert_eq!(**t, llvm::Ty::Namedt("P".into()));
+                    match path.as_slice() {
+                        [Operand::Const(0), Operand::Const(idx)] => Some(*idx),
+                        _ => panic!("bad path {:?}", path),
+                    }
+                }
+                _ => None,
+            })
+            .collect();
+        // y = 2; x = 1 (literal), p.y = 5, p.x
+        assert_eq!(field_geps, vec![1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_locals_are_hoisted_allocas() {
+        let p = compile("int f(int a) { while (a > 0) { var b = a; a = a - b; } return a; }");
+        let (entry, blocks) = &fdecl(&p, "f").f_cfg;
+        let allocas = |b: &llvm::Block| {
+            b.insns
+                .iter()
+                .filter(|(_, i)| matches!(i, Insn::Alloca(_)))
+                .count()
+        };
+        assert_eq!(allocas(entry), 2);
+        assert!(blocks.iter().all(|(_, b)| allocas(b) == 0));
+    }
+
+    #[test]
+    fn test_global_initializers() {
+        let p = compile(
+            "struct P { int x; string s; }
+            global n = 3;
+            global m = -3;
+            global s = \"hi\";
+            global a = new int[]{1, 2};
+            global q = new P { s = \"q\"; x = 4 };",
+        );
+        let gdecl = |name: &str| &p.gdecls.iter().find(|(n, _)| n == name).unwrap().1;
+
+        assert_eq!(gdecl("n"), &(llvm::Ty::I64, llvm::Ginit::GInt(3)));
+        assert_eq!(gdecl("m"), &(llvm::Ty::I64, llvm::Ginit::GInt(-3)));
+        assert!(matches!(gdecl("s"), (_, llvm::Ginit::GBitcast(_, _, _))));
+        assert_eq!(gdecl("a").0, ptr(cmp_arr_ty(&Ty::TInt)));
+
+        // fields in declaration order
+        let (t, init) = gdecl("q");
+        assert_eq!(*t, ptr(llvm::Ty::Namedt("P".into())));
+        let llvm::Ginit::GGid(data) = init else {
+            panic!("got {:?}", init)
+        };
+        match &gdecl(data).1 {
+            llvm::Ginit::GStruct(fields) => {
+                assert_eq!(fields[0], (llvm::Ty::I64, llvm::Ginit::GInt(4)));
+                assert_eq!(fields[1].0, ptr(llvm::Ty::I8));
+            }
+            g => panic!("got {:?}", g),
+        }
+    }
+
+    #[test]
+    fn test_global_initializers_with_code() {
+        let p = compile(
+            "int f() { return 7; }
+            global n = 3;
+            global a = new int[n];
+            global x = f() + n;",
+        );
+        let gdecl = |name: &str| &p.gdecls.iter().find(|(n, _)| n == name).unwrap().1;
+
+        // laid out zeroed, then set by the init function, in declaration order
+        assert_eq!(gdecl("n"), &(llvm::Ty::I64, llvm::Ginit::GInt(3)));
+        assert_eq!(
+            gdecl("a"),
+            &(ptr(cmp_arr_ty(&Ty::TInt)), llvm::Ginit::GNull)
+        );
+        assert_eq!(gdecl("x"), &(llvm::Ty::I64, llvm::Ginit::GInt(0)));
+
+        let init = fdecl(&p, OAT_INIT_GLOBALS);
+        assert_eq!(init.f_ty, (vec![], llvm::Ty::Void));
+        let stored: Vec<_> = insns(init)
+            .into_iter()
+            .filter_map(|i| match i {
+                Insn::Store(_, _, Operand::Gid(g)) => Some(g.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(stored, vec!["a", "x"]);
+
+        // no init function when every initializer is a constant
+        let p = compile("global n = 3; global s = \"s\";");
+        assert!(p.fdecls.iter().all(|(n, _)| n != OAT_INIT_GLOBALS));
+    }
+
+    #[test]
+    fn test_index_is_bounds_checked() {
+        let src = "int f(int[] a) { a[1] = 2; return a[0] + length(a); }";
+        let p = compile(src);
+        let f = fdecl(&p, "f");
+
+        // one trap per indexing expression, carrying its span
+        let spans: Vec<&str> = insns(f)
+            .into_iter()
+            .filter_map(|i| match i {
+                Insn::Call(_, Operand::Gid(g), args) if g == OAT_BOUNDS_FAIL => {
+                    match args.as_slice() {
+                        [_, _, (_, Operand::Const(start)), (_, Operand::Const(end))] => {
+                            Some(&src[*start as usize..*end as usize])
+                        }
+                        _ => panic!("bad args {:?}", args),
+                    }
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(spans, vec!["a[1]", "a[0]"]);
+
+        // entry, then per check: trap block and in-bounds block
+        let (_, blocks) = &f.f_cfg;
+        assert_eq!(blocks.len(), 4);
+    }
+
+    #[test]
+    fn test_new_array_traps_on_negative_length() {
+        let src = "int[] f(int n) { var a = new int[]{1}; return new int[n]; }";
+        let p = compile(src);
+        let f = fdecl(&p, "f");
+
+        // only `new int[n]` is checked: a literal's length is known
+        let spans: Vec<&str> = insns(f)
+            .into_iter()
+            .filter_map(|i| match i {
+                Insn::Call(_, Operand::Gid(g), args) if g == OAT_NEGATIVE_LENGTH => {
+                    match args.as_slice() {
+                        [_, (_, Operand::Const(start)), (_, Operand::Const(end))] => {
+                            Some(&src[*start as usize..*end as usize])
+                        }
+                        _ => panic!("bad args {:?}", args),
+                    }
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(spans, vec!["new int[n]"]);
+    }
+
+    #[test]
+    fn test_division_traps_on_zero() {
+        let src = "int f(int a, int b) { return a / b + a % 3; }";
+        let p = compile(src);
+        let f = fdecl(&p, "f");
+
+        // one zero check per division, carrying its span
+        let spans: Vec<&str> = insns(f)
+            .into_iter()
+            .filter_map(|i| match i {
+                Insn::Call(_, Operand::Gid(g), args) if g == OAT_DIV_BY_ZERO => {
+                    match args.as_slice() {
+                        [(_, Operand::Const(start)), (_, Operand::Const(end))] => {
+                            Some(&src[*start as usize..*end as usize])
+                        }
+                        _ => panic!("bad args {:?}", args),
+                    }
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(spans, vec!["a / b", "a % 3"]);
+
+        let bops: Vec<_> = insns(f)
+            .into_iter()
+            .filter_map(|i| match i {
+                Insn::Binop(b, _, _, _) => Some(b),
+                _ => None,
+            })
+            .collect();
+        assert!(matches!(
+            bops.as_slice(),
+            [llvm::Bop::SDiv, llvm::Bop::SRem, llvm::Bop::Add]
+        ));
+    }
+
+    #[test]
+    fn test_conditional_merges_through_slot() {
+        let p = compile(
+            "struct P { int x; } struct Q { int x; int y; }
+             int f(bool b, P p, Q q) { var r = b ? q : p; return r.x; }",
+        );
+        let f = fdecl(&p, "f");
+        let (entry, blocks) = &f.f_cfg;
+        let labels: Vec<_> = blocks.iter().map(|(l, _)| l.as_str()).collect();
+        assert!(labels[0].starts_with("_then"));
+        assert!(labels[1].starts_with("_else"));
+        assert!(labels[2].starts_with("_merge"));
+
+        // both branches store into one slot of the conditional's type, P, the
+        // then-branch casting its Q to it
+        let q = ptr(llvm::Ty::Namedt("Q".into()));
+        let pt = ptr(llvm::Ty::Namedt("P".into()));
+        assert!(
+            entry
+                .insns
+                .iter()
+                .any(|(_, i)| *i == Insn::Alloca(pt.clone()))
+        );
+        let stores = |b: &llvm::Block| {
+            b.insns
+                .iter()
+                .filter(|(_, i)| matches!(i, Insn::Store(t, _, _) if *t == pt))
+                .count()
+        };
+        assert_eq!(stores(&blocks[0].1), 1);
+        assert_eq!(stores(&blocks[1].1), 1);
+        assert!(
+            blocks[0]
+                .1
+                .insns
+                .iter()
+                .any(|(_, i)| matches!(i, Insn::Bitcast(from, _, to) if *from == q && *to == pt))
+        );
+        assert!(
+            blocks[2]
+                .1
+                .insns
+                .iter()
+                .any(|(_, i)| matches!(i, Insn::Load(t, _) if *t == ptr(pt.clone())))
+        );
+    }
+
+    #[test]
+    fn test_compound_assignment_evaluates_lhs_once() {
+        let p = compile(
+            "int g() { return 0; }
+             void f(int[] a) { a[g()] += 2; a[g()]++; return; }",
+        );
+        let f = fdecl(&p, "f");
+        let count = |pred: &dyn Fn(&Insn) -> bool| insns(f).into_iter().filter(|i| pred(i)).count();
+
+        // per statement: one call to g, one bounds check, one element address
+        let calls_g = count(&|i| matches!(i, Insn::Call(_, Operand::Gid(g), _) if g == "g"));
+        let checks =
+            count(&|i| matches!(i, Insn::Call(_, Operand::Gid(g), _) if g == OAT_BOUNDS_FAIL));
+        let geps = count(&|i| matches!(i, Insn::Gep(_, _, path) if path.len() == 3));
+        assert_eq!((calls_g, checks, geps), (2, 2, 2));
+
+        let bops: Vec<_> = insns(f)
+            .into_iter()
+            .filter_map(|i| match i {
+                Insn::Binop(b, _, _, r) => Some((b, r)),
+                _ => None,
+            })
+            .filter(|(b, _)| matches!(b, llvm::Bop::Add))
+            .collect();
+        assert!(matches!(
+            bops.as_slice(),
+            [(_, Operand::Const(2)), (_, Operand::Const(1))]
+        ));
+    }
+
+    #[test]
+    fn test_break_and_continue_targets() {
+        // where the (innermost) loop body jumps to
+        let body_target = |body: &str| {
+            let p = compile(&format!("void f() {{ {} return; }}", body));
+            let (_, blocks) = &fdecl(&p, "f").f_cfg;
+            let (_, b) = blocks
+                .iter()
+                .rev()
+                .find(|(l, _)| l.starts_with("_body"))
+                .unwrap();
+            match &b.term.1 {
+                Terminator::Br(l) => l
+                    .trim_start_matches('_')
+                    .trim_end_matches(char::is_numeric)
+                    .to_string(),
+                t => panic!("got {:?}", t),
+            }
+        };
+
+        assert_eq!(body_target("while (true) { continue; }"), "cond");
+        assert_eq!(body_target("while (true) { break; }"), "post");
+        assert_eq!(body_target("for (;;) { continue; }"), "step");
+        assert_eq!(body_target("for (;;) { break; }"), "post");
+        // inside a while nested in a for
+        assert_eq!(
+            body_target("for (;;) { while (true) { continue; } }"),
+            "cond"
+        );
+    }
+
+    #[test]
+    fn test_if_q_null_check() {
+        let p = compile("int f(string? s) { if? (string t = s) { return 1; } return 0; }");
+        let (entry, blocks) = &fdecl(&p, "f").f_cfg;
+
+        // the entry compares against null, and null goes to the else branch
+        assert!(
+            entry
+                .insns
+                .iter()
+                .any(|(_, i)| matches!(i, Insn::Icmp(llvm::Cnd::Eq, _, _, Operand::Null)))
+        );
+        match &entry.term.1 {
+            Terminator::Cbr(_, lnull, lnonnull) => {
+                assert!(lnull.starts_with("_else"));
+                assert!(lnonnull.starts_with("_then"));
+            }
+            t => panic!("got {:?}", t),
+        }
+        // t gets its own slot
+        assert!(
+            blocks
+                .iter()
+                .all(|(_, b)| b.insns.iter().all(|(_, i)| !matches!(i, Insn::Alloca(_))))
+        );
+        assert_eq!(
+            entry
+                .insns
+                .iter()
+                .filter(|(_, i)| matches!(i, Insn::Alloca(_)))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_for_each_is_unchecked_indexed_loop() {
+        let p = compile("int f(int[] a) { var s = 0; for (x in a) { s = s + x; } return s; }");
+        let f = fdecl(&p, "f");
+        let is = insns(f);
+
+        // the loop bound is the length header, read once before the loop
+        let (entry, _) = &f.f_cfg;
+        assert!(entry.insns.iter().any(|(_, i)| matches!(
+            i,
+            Insn::Gep(_, _, path) if path == &vec![Operand::Const(0), Operand::Const(0)]
+        )));
+        // elements are read without a bounds check: i < length(a) already holds
+        assert!(
+            !is.iter()
+                .any(|i| matches!(i, Insn::Call(_, Operand::Gid(g), _) if g == OAT_BOUNDS_FAIL))
+        );
+        // slots for a, s, the index and x
+        assert_eq!(
+            entry
+                .insns
+                .iter()
+                .filter(|(_, i)| matches!(i, Insn::Alloca(_)))
+                .count(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_runtime_edecls() {
+        let p = compile("void f() { return; }");
+        let names: Vec<_> = p.edecls.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                OAT_MALLOC,
+                OAT_ALLOC_ARRAY,
+                OAT_BOUNDS_FAIL,
+                OAT_NEGATIVE_LENGTH,
+                OAT_DIV_BY_ZERO
+            ]
+        );
+    }
+}
@@ -1 +1,305 @@
+//! Frontend : ast::Prog → llvm::Prog
+//!
+//! lowers a (typechecked) Oat program to LLVMlite. code for a function body is
+//! first emitted as a flat stream of labels, instructions and terminators,
+//! then cut into basic blocks by `Ctxt::take_cfg`.
+//!
+//! memory layout:
+//!   - every local lives in an `alloca` slot, hoisted to the entry block
+//!   - strings are `i8*` into a global, NUL-terminated
+//!   - arrays are `{ i64, [0 x T] }*`: the length, then the elements
+//!   - structs are `%S*`, with `%S = { field types, in declaration order }`
+//!   - globals with constant initializers are static data; the others start
+//!     zeroed and are set by `oat_init_globals`, which `main` runs first
+//!
+//! heap objects come from the Oat runtime (`oat_malloc`, `oat_alloc_array`),
+//! and an out-of-bounds `a[i]` calls `oat_bounds_fail` with the span of the
+//! indexing expression; `new T[n]` with `n < 0` calls `oat_negative_length`
+//! with the span of the allocation; `a / b` and `a % b` with `b == 0` call
+//! `oat_div_by_zero` with the span of the division. `runtime/runtime.c`
+//! implements these for linking.
+//!
+//! the input is assumed to be well-typed: anything the typechecker rules out
+//! is a panic here, as in the backend. the types of expressions and calls come
+//! from the typechecker's `TypeTable`, looked up by node id.
 
+use std::collections::HashMap;
+
+use ast::*;
+use typechecker::TypeTable;
+
+// --- instruction stream ---
+
+/// one element of a function's instruction stream
+#[derive(Debug, Clone)]
+pub enum Elt {
+    L(llvm::Lbl),              // start of a block
+    I(llvm::Uid, llvm::Insn),  // instruction
+    T(llvm::Terminator),       // end of a block
+    G(llvm::Gid, llvm::Gdecl), // global data, hoisted to the program (string literals)
+    E(llvm::Uid, llvm::Insn),  // instruction hoisted to the entry block (allocas)
+}
+
+pub type Stream = Vec<Elt>;
+
+// --- compilation context ---
+
+/// llvm type and operand an Oat identifier compiles to:
+///   locals & global variables: a pointer to their storage
+///   functions: the function itself, `Ptr(Fun(..))`
+pub type Binding = (llvm::Ty, llvm::Operand);
+
+#[derive(Debug, Clone)]
+pub struct Ctxt<'a> {
+    types: &'a TypeTable,
+    locals: Vec<HashMap<IdTy, Binding>>, // scoped like the typechecker's
+    globals: HashMap<IdTy, Binding>,
+    structs: HashMap<IdTy, Vec<Field>>,
+    loops: Vec<(llvm::Lbl, llvm::Lbl)>, // (break, continue) targets, innermost last
+    stream: Stream,
+    next_id: usize,
+}
+
+impl<'a> Ctxt<'a> {
+    pub fn new(types: &'a TypeTable) -> Self {
+        Self {
+            types,
+            locals: vec![HashMap::new()],
+            globals: HashMap::new(),
+            structs: HashMap::new(),
+            loops: Vec::new(),
+            stream: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    // ----- types -----
+
+    /// the type the typechecker gave `e`
+    pub fn type_of(&self, e: &Node<SExp>) -> &Ty {
+        match self.types.exps.get(e) {
+            Some(t) => &t.node,
+            None => panic!("frontend: untyped expression {:?}", e.elt.node),
+        }
+    }
+
+    /// llvm type of the function a call (expression or statement) resolved to
+    pub fn call_type<T: std::fmt::Debug>(&self, call: &Node<T>) -> llvm::Fty {
+        match self.types.calls.get(call) {
+            Some((args, ret)) => cmp_fty(args, &ret.node),
+            None => panic!("frontend: untyped call {:?}", call.elt),
+        }
+    }
+
+    // ----- names -----
+
+    /// fresh name, unique across the whole program
+    pub fn gensym(&mut self, prefix: &str) -> llvm::Uid {
+        self.next_id += 1;
+        format!("_{}{}", prefix, self.next_id).into()
+    }
+
+    // ----- locals -----
+    pub fn push_scope(&mut self) {
+        self.locals.push(HashMap::new());
+    }
+
+    pub fn pop_scope(&mut self) {
+        self.locals.pop();
+    }
+
+    pub fn add_local(&mut self, id: IdTy, b: Binding) {
+        self.locals.last_mut().unwrap().insert(id, b);
+    }
+
+    // ----- globals -----
+    pub fn add_global(&mut self, id: IdTy, b: Binding) {
+        self.globals.insert(id, b);
+    }
+
+    // local? global
+    pub fn lookup(&self, id: IdTy) -> &Binding {
+        self.locals
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&id))
+            .or_else(|| self.globals.get(&id))
+            .unwrap_or_else(|| panic!("frontend: unbound identifier {}", id))
+    }
+
+    // ----- structs -----
+    pub fn add_struct(&mut self, id: IdTy, fields: Vec<Field>) {
+        self.structs.insert(id, fields);
+    }
+
+    /// position and type of a field (its gep index)
+    pub fn lookup_field(&self, st_name: IdTy, f_name: IdTy) -> (usize, &Ty) {
+        let fields = self
+            .structs
+            .get(&st_name)
+            .unwrap_or_else(|| panic!("frontend: unbound struct {}", st_name));
+        fields
+            .iter()
+            .enumerate()
+            .find(|(_, f)| f.field_name == f_name)
+            .map(|(i, f)| (i, &f.field_type))
+            .unwrap_or_else(|| panic!("frontend: struct {} has no field {}", st_name, f_name))
+    }
+
+    pub fn struct_fields(&self, st_name: IdTy) -> &[Field] {
+        self.structs
+            .get(&st_name)
+            .unwrap_or_else(|| panic!("frontend: unbound struct {}", st_name))
+    }
+
+    // ----- loops -----
+    pub fn push_loop(&mut self, brk: llvm::Lbl, cont: llvm::Lbl) {
+        self.loops.push((brk, cont));
+    }
+
+    pub fn pop_loop(&mut self) {
+        self.loops.pop();
+    }
+
+    /// (break, continue) labels of the innermost loop
+    pub fn innermost_loop(&self) -> &(llvm::Lbl, llvm::Lbl) {
+        self.loops
+            .last()
+            .expect("frontend: break or continue outside of a loop")
+    }
+
+    // ----- stream -----
+    pub fn emit(&mut self, elt: Elt) {
+        self.stream.push(elt);
+    }
+
+    /// emit `uid = insn` with a fresh uid and return it as an operand
+    pub fn insn(&mut self, prefix: &str, insn: llvm::Insn) -> llvm::Operand {
+        let uid = self.gensym(prefix);
+        self.emit(Elt::I(uid, insn));
+        llvm::Operand::Id(uid)
+    }
+
+    pub fn label(&mut self, lbl: &llvm::Lbl) {
+        self.emit(Elt::L(*lbl));
+    }
+
+    pub fn term(&mut self, t: llvm::Terminator) {
+        self.emit(Elt::T(t));
+    }
+
+    /// cut the stream emitted so far into a cfg, returning it with the
+    /// hoisted globals. code after a terminator and before the next label is
+    /// unreachable and dropped; a block still open at the end returns.
+    pub fn take_cfg(&mut self, ret_ty: &llvm::Ty) -> (llvm::Cfg, Vec<(llvm::Gid, llvm::Gdecl)>) {
+        let stream = std::mem::take(&mut self.stream);
+        let mut gdecls = Vec::new();
+        let mut hoisted = Vec::new();
+        let mut blocks: Blocks = Vec::new();
+
+        // the open block: its label (None for the entry) and instructions
+        let mut cur: Option<OpenBlock> = Some((None, Vec::new()));
+
+        for elt in stream {
+            match elt {
+                Elt::L(l) => {
+                    // fall through into the new block
+                    if let Some((lbl, insns)) = cur.take() {
+                        close_block(&mut blocks, lbl, insns, llvm::Terminator::Br(l));
+                    }
+                    cur = Some((Some(l), Vec::new()));
+                }
+                Elt::I(uid, insn) => {
+                    if let Some((_, insns)) = cur.as_mut() {
+                        insns.push((uid, insn));
+                    }
+                }
+                Elt::T(t) => {
+                    if let Some((lbl, insns)) = cur.take() {
+                        close_block(&mut blocks, lbl, insns, t);
+                    }
+                }
+                Elt::G(gid, gdecl) => gdecls.push((gid, gdecl)),
+                Elt::E(uid, insn) => hoisted.push((uid, insn)),
+            }
+        }
+        if let Some((lbl, insns)) = cur.take() {
+            let t = match ret_ty {
+                llvm::Ty::Void => llvm::Terminator::Ret(llvm::Ty::Void, None),
+                t => llvm::Terminator::Ret(t.clone(), Some(llvm::Operand::Const(0))),
+            };
+            close_block(&mut blocks, lbl, insns, t);
+        }
+
+        let mut blocks = blocks.into_iter();
+        let (_, mut entry) = blocks.next().expect("frontend: empty stream");
+        hoisted.append(&mut entry.insns);
+        entry.insns = hoisted;
+
+        let labeled = blocks
+            .map(|(lbl, b)| (lbl.expect("frontend: unlabeled block"), b))
+            .collect();
+        ((entry, labeled), gdecls)
+    }
+}
+
+type Blocks = Vec<(Option<llvm::Lbl>, llvm::Block)>;
+type OpenBlock = (Option<llvm::Lbl>, Vec<(llvm::Uid, llvm::Insn)>);
+
+fn close_block(
+    blocks: &mut Blocks,
+    lbl: Option<llvm::Lbl>,
+    insns: Vec<(llvm::Uid, llvm::Insn)>,
+    t: llvm::Terminator,
+) {
+    let term_uid = format!("_term{}", blocks.len()).into();
+    blocks.push((lbl, llvm::Block::new(insns, (term_uid, t))));
+}
+
+// --- types ---
+
+pub fn cmp_ty(t: &Ty) -> llvm::Ty {
+    match t {
+        Ty::TBool => llvm::Ty::I1,
+        Ty::TInt => llvm::Ty::I64,
+        Ty::TRef(r) | Ty::TNullRef(r) => llvm::Ty::Ptr(Box::new(cmp_rty(&r.node))),
+    }
+}
+
+/// pointee of a reference type
+pub fn cmp_rty(r: &RefTy) -> llvm::Ty {
+    match r {
+        RefTy::RString => llvm::Ty::I8,
+        RefTy::RArray(t) => cmp_arr_ty(&t.node),
+        RefTy::RStruct(id) => llvm::Ty::Namedt(*id),
+        RefTy::RFun(args, ret) => {
+            let (args, ret) = cmp_fty(args, &ret.node);
+            llvm::Ty::Fun(args, Box::new(ret))
+        }
+    }
+}
+
+/// { i64, [0 x t] }
+pub fn cmp_arr_ty(t: &Ty) -> llvm::Ty {
+    llvm::Ty::Struct(vec![llvm::Ty::I64, llvm::Ty::Array(0, Box::new(cmp_ty(t)))])
+}
+
+pub fn cmp_ret_ty(r: &RetTy) -> llvm::Ty {
+    match r {
+        RetTy::RetVoid => llvm::Ty::Void,
+        RetTy::RetVal(t) => cmp_ty(&t.node),
+    }
+}
+
+pub fn cmp_fty(args: &[STy], ret: &RetTy) -> llvm::Fty {
+    (
+        args.iter().map(|t| cmp_ty(&t.node)).collect(),
+        cmp_ret_ty(ret),
+    )
+}
+
+// Frontend implementation module
+pub mod frontend;
+
+// Re-export main compilation function for external use
+pub use frontend::compile_prog;
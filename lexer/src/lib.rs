@@ -1 +1,881 @@
+//! Lexer : Oat source → tokens
+//!
+//! every token carries a `common::Span` (byte offsets into the source),
+//! so the parser can hand precise locations down to `ast::Node::loc`
+//!
+//! lexing never stops at the first problem: a bad character, string or
+//! literal is recorded as a `LexError` and replaced by `Token::Error`,
+//! so one run reports every lexical error in the file
+//!
+//! `Lexer::with_trivia` / `tokenize_lossless` keep whitespace and comments
+//! attached to the following token, for tools that need to print the
+//! source back byte-for-byte (formatter, language server, doc extraction)
 
+use common::{LexError, LexErrorKind, Span, Spanned, Symbol};
+use std::fmt;
+
+// --- tokens ---
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    // literals & identifiers
+    Int(i64),
+    Str(String),
+    Bool(bool),
+    Ident(Symbol),
+
+    // keywords
+    Struct,
+    Null,
+    Void,
+    TInt,
+    TBool,
+    TString,
+    If,
+    IfQ, // if?
+    Else,
+    While,
+    For,
+    Return,
+    Break,
+    Continue,
+    Var,
+    Global,
+    New,
+    Length,
+
+    // punctuation
+    Semi,     // ;
+    Comma,    // ,
+    LBrace,   // {
+    RBrace,   // }
+    LParen,   // (
+    RParen,   // )
+    LBracket, // [
+    RBracket, // ]
+    Dot,      // .
+    Question, // ?
+    Colon,    // :
+    Arrow,    // ->
+
+    // operators
+    Plus,    // +
+    Dash,    // -
+    Star,    // *
+    Slash,   // /
+    Percent, // %
+    Eq,      // =
+    EqEq,    // ==
+    Bang,    // !
+    BangEq,  // !=
+    Tilde,   // ~
+    Lt,      // <
+    LtEq,    // <=
+    Gt,      // >
+    GtEq,    // >=
+    LtLt,    // <<
+    GtGt,    // >>
+    GtGtGt,  // >>>
+    Amper,   // &
+    Bar,     // |
+    IAnd,    // [&]
+    IOr,     // [|]
+
+    // compound assignment and increment/decrement
+    PlusEq,    // +=
+    DashEq,    // -=
+    StarEq,    // *=
+    SlashEq,   // /=
+    PercentEq, // %=
+    LtLtEq,    // <<=
+    GtGtEq,    // >>=
+    GtGtGtEq,  // >>>=
+    AmperEq,   // &=
+    BarEq,     // |=
+    IAndEq,    // [&]=
+    IOrEq,     // [|]=
+    PlusPlus,  // ++
+    DashDash,  // --
+
+    Error, // placeholder for input that failed to lex (see `Lexer::errors`)
+    Eof,
+}
+
+pub type SToken = Spanned<Token>;
+
+/// character after a `\\` in string & char literals
+fn unescape(e: char) -> Option<char> {
+    match e {
+        'n' => Some('\n'),
+        't' => Some('\t'),
+        'r' => Some('\r'),
+        '0' => Some('\0'),
+        '\\' => Some('\\'),
+        '"' => Some('"'),
+        '\'' => Some('\''),
+        _ => None,
+    }
+}
+
+/// reserved words → keyword tokens
+fn keyword(s: &str) -> Option<Token> {
+    let t = match s {
+        "struct" => Token::Struct,
+        "null" => Token::Null,
+        "void" => Token::Void,
+        "int" => Token::TInt,
+        "bool" => Token::TBool,
+        "string" => Token::TString,
+        "if" => Token::If,
+        "else" => Token::Else,
+        "while" => Token::While,
+        "for" => Token::For,
+        "return" => Token::Return,
+        "break" => Token::Break,
+        "continue" => Token::Continue,
+        "var" => Token::Var,
+        "global" => Token::Global,
+        "new" => Token::New,
+        "length" => Token::Length,
+        "true" => Token::Bool(true),
+        "false" => Token::Bool(false),
+        _ => return None,
+    };
+    Some(t)
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Token::Int(i) => return write!(f, "{}", i),
+            Token::Str(s) => return write!(f, "\"{}\"", s.escape_default()),
+            Token::Bool(b) => return write!(f, "{}", b),
+            Token::Ident(id) => return write!(f, "{}", id),
+            Token::Struct => "struct",
+            Token::Null => "null",
+            Token::Void => "void",
+            Token::TInt => "int",
+            Token::TBool => "bool",
+            Token::TString => "string",
+            Token::If => "if",
+            Token::IfQ => "if?",
+            Token::Else => "else",
+            Token::While => "while",
+            Token::For => "for",
+            Token::Return => "return",
+            Token::Break => "break",
+            Token::Continue => "continue",
+            Token::Var => "var",
+            Token::Global => "global",
+            Token::New => "new",
+            Token::Length => "length",
+            Token::Semi => ";",
+            Token::Comma => ",",
+            Token::LBrace => "{",
+            Token::RBrace => "}",
+            Token::LParen => "(",
+            Token::RParen => ")",
+            Token::LBracket => "[",
+            Token::RBracket => "]",
+            Token::Dot => ".",
+            Token::Question => "?",
+            Token::Colon => ":",
+            Token::Arrow => "->",
+            Token::Plus => "+",
+            Token::Dash => "-",
+            Token::Star => "*",
+            Token::Slash => "/",
+            Token::Percent => "%",
+            Token::Eq => "=",
+            Token::EqEq => "==",
+            Token::Bang => "!",
+            Token::BangEq => "!=",
+            Token::Tilde => "~",
+            Token::Lt => "<",
+            Token::LtEq => "<=",
+            Token::Gt => ">",
+            Token::GtEq => ">=",
+            Token::LtLt => "<<",
+            Token::GtGt => ">>",
+            Token::GtGtGt => ">>>",
+            Token::Amper => "&",
+            Token::Bar => "|",
+            Token::IAnd => "[&]",
+            Token::IOr => "[|]",
+            Token::PlusEq => "+=",
+            Token::DashEq => "-=",
+            Token::StarEq => "*=",
+            Token::SlashEq => "/=",
+            Token::PercentEq => "%=",
+            Token::LtLtEq => "<<=",
+            Token::GtGtEq => ">>=",
+            Token::GtGtGtEq => ">>>=",
+            Token::AmperEq => "&=",
+            Token::BarEq => "|=",
+            Token::IAndEq => "[&]=",
+            Token::IOrEq => "[|]=",
+            Token::PlusPlus => "++",
+            Token::DashDash => "--",
+            Token::Error => "<error>",
+            Token::Eof => "end of file",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// --- trivia ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    LineComment,  // `// ...` (up to, not including, the newline)
+    BlockComment, // `/* ... */`
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub span: Span,
+}
+
+/// a token together with the trivia in front of it and its exact source text
+///
+/// trivia at the end of the file is attached to the `Token::Eof` token
+#[derive(Debug, Clone, PartialEq)]
+pub struct LosslessToken {
+    pub leading: Vec<Trivia>,
+    pub token: SToken,
+    pub text: String,
+}
+
+impl fmt::Display for LosslessToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for t in &self.leading {
+            write!(f, "{}", t.text)?;
+        }
+        write!(f, "{}", self.text)
+    }
+}
+
+// --- lexer ---
+
+pub struct Lexer<'a> {
+    src: &'a str,
+    pos: usize,
+    errors: Vec<LexError>,
+    keep_trivia: bool,
+    trivia: Vec<Trivia>, // trivia seen since the last token (only when keep_trivia)
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(src: &'a str) -> Self {
+        Self {
+            src,
+            pos: 0,
+            errors: Vec::new(),
+            keep_trivia: false,
+            trivia: Vec::new(),
+        }
+    }
+
+    /// lossless mode: trivia is collected instead of dropped (see `next_lossless`)
+    pub fn with_trivia(src: &'a str) -> Self {
+        Self {
+            keep_trivia: true,
+            ..Self::new(src)
+        }
+    }
+
+    /// errors recorded so far
+    pub fn errors(&self) -> &[LexError] {
+        &self.errors
+    }
+
+    pub fn into_errors(self) -> Vec<LexError> {
+        self.errors
+    }
+
+    fn error(&mut self, msg: impl Into<String>, span: Span, kind: LexErrorKind) {
+        self.errors.push(LexError::new(msg.into(), span, kind));
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
+
+    fn peek_at(&self, n: usize) -> Option<u8> {
+        self.src.as_bytes().get(self.pos + n).copied()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        self.src[self.pos..].starts_with(s)
+    }
+
+    /// skip whitespace, `// line` and `/* block */` comments
+    /// (recording them in lossless mode)
+    fn skip_trivia(&mut self) {
+        loop {
+            let start = self.pos;
+            let kind = match self.peek() {
+                Some(c) if c.is_ascii_whitespace() => {
+                    while matches!(self.peek(), Some(c) if c.is_ascii_whitespace()) {
+                        self.pos += 1;
+                    }
+                    TriviaKind::Whitespace
+                }
+                Some(b'/') if self.peek_at(1) == Some(b'/') => {
+                    while !matches!(self.peek(), None | Some(b'\n')) {
+                        self.pos += 1;
+                    }
+                    TriviaKind::LineComment
+                }
+                Some(b'/') if self.peek_at(1) == Some(b'*') => {
+                    match self.src[self.pos + 2..].find("*/") {
+                        Some(i) => self.pos += i + 4,
+                        None => {
+                            self.pos = self.src.len();
+                            self.error(
+                                "unterminated block comment",
+                                Span::new(start, self.pos),
+                                LexErrorKind::UnterminatedComment,
+                            );
+                        }
+                    }
+                    TriviaKind::BlockComment
+                }
+                _ => return,
+            };
+
+            if self.keep_trivia {
+                self.trivia.push(Trivia {
+                    kind,
+                    text: self.src[start..self.pos].to_string(),
+                    span: Span::new(start, self.pos),
+                });
+            }
+        }
+    }
+
+    /// produce the next token (`Token::Eof` once the input is exhausted)
+    pub fn next_token(&mut self) -> SToken {
+        self.skip_trivia();
+        let start = self.pos;
+
+        let c = match self.peek() {
+            None => return Spanned::new(Span::new(start, start), Token::Eof),
+            Some(c) => c,
+        };
+
+        let tok = if c.is_ascii_alphabetic() || c == b'_' {
+            self.lex_word()
+        } else if c.is_ascii_digit() {
+            self.lex_int()
+        } else if c == b'"' {
+            self.lex_str()
+        } else if c == b'\'' {
+            self.lex_char()
+        } else {
+            self.lex_symbol()
+        };
+
+        Spanned::new(Span::new(start, self.pos), tok)
+    }
+
+    /// next token with its leading trivia and source text
+    /// (trivia is only collected by a lexer built with `with_trivia`)
+    pub fn next_lossless(&mut self) -> LosslessToken {
+        let token = self.next_token();
+        let text = self.src[token.span.start..token.span.end].to_string();
+        LosslessToken {
+            leading: std::mem::take(&mut self.trivia),
+            token,
+            text,
+        }
+    }
+
+    /// identifiers & keywords (plus the `if?` keyword)
+    fn lex_word(&mut self) -> Token {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == b'_') {
+            self.pos += 1;
+        }
+        let word = &self.src[start..self.pos];
+
+        if word == "if" && self.peek() == Some(b'?') {
+            self.pos += 1;
+            return Token::IfQ;
+        }
+        keyword(word).unwrap_or_else(|| Token::Ident(word.into()))
+    }
+
+    /// integer literal: decimal, `0x` hex or `0b` binary, with `_` separators
+    ///
+    /// decimal literals must fit in the i64 behind `ast::Exp::CInt`, hex and
+    /// binary ones may use all 64 bits (`0xFFFF_FFFF_FFFF_FFFF` is -1)
+    fn lex_int(&mut self) -> Token {
+        let start = self.pos;
+        let radix = match (self.peek(), self.peek_at(1)) {
+            (Some(b'0'), Some(b'x' | b'X')) => 16,
+            (Some(b'0'), Some(b'b' | b'B')) => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            self.pos += 2;
+        }
+        let digits_start = self.pos;
+
+        // take the whole alphanumeric run, so `12ab` is one bad literal
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == b'_') {
+            self.pos += 1;
+        }
+        let span = Span::new(start, self.pos);
+        let literal = self.src[start..self.pos].to_string();
+        let digits: String = self.src[digits_start..self.pos]
+            .chars()
+            .filter(|c| *c != '_')
+            .collect();
+
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+            self.error(
+                format!("malformed integer literal {}", literal),
+                span,
+                LexErrorKind::MalformedInt { literal },
+            );
+            return Token::Error;
+        }
+
+        let value = if radix == 10 {
+            digits.parse::<i64>().ok()
+        } else {
+            u64::from_str_radix(&digits, radix).ok().map(|u| u as i64)
+        };
+        match value {
+            Some(i) => Token::Int(i),
+            None => {
+                self.error(
+                    format!("integer literal {} does not fit in 64 bits", literal),
+                    span,
+                    LexErrorKind::IntOverflow { literal },
+                );
+                Token::Error
+            }
+        }
+    }
+
+    /// character literal `'a'` / `'\\n'`, lexed as its code point
+    fn lex_char(&mut self) -> Token {
+        let start = self.pos;
+        self.pos += 1; // opening quote
+
+        let value = match self.src[self.pos..].chars().next() {
+            None | Some('\n') | Some('\'') => None,
+            Some('\\') => {
+                self.pos += 1;
+                let e = self.src[self.pos..].chars().next().filter(|e| *e != '\n');
+                self.pos += e.map_or(0, |e| e.len_utf8());
+                e.and_then(unescape)
+            }
+            Some(c) => {
+                self.pos += c.len_utf8();
+                Some(c)
+            }
+        };
+
+        if let (Some(c), Some(b'\'')) = (value, self.peek()) {
+            self.pos += 1;
+            return Token::Int(c as i64);
+        }
+
+        // resync at the closing quote, or at the end of the line
+        let rest = &self.src[self.pos..];
+        let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+        self.pos += line.find('\'').map_or(line.len(), |i| i + 1);
+        self.error(
+            "character literal must be exactly one character or escape",
+            Span::new(start, self.pos),
+            LexErrorKind::BadCharLiteral,
+        );
+        Token::Error
+    }
+
+    /// string literal, with the usual escapes; must close on the same line
+    ///
+    /// a bad escape is reported but the string token is kept,
+    /// an unterminated string becomes `Token::Error` up to the end of the line
+    fn lex_str(&mut self) -> Token {
+        let start = self.pos;
+        self.pos += 1; // opening quote
+        let mut s = String::new();
+
+        loop {
+            let c = match self.src[self.pos..].chars().next() {
+                None | Some('\n') => {
+                    self.error(
+                        "unterminated string literal",
+                        Span::new(start, self.pos),
+                        LexErrorKind::UnterminatedString,
+                    );
+                    return Token::Error;
+                }
+                Some(c) => c,
+            };
+            self.pos += c.len_utf8();
+
+            match c {
+                '"' => return Token::Str(s),
+                '\\' => {
+                    let esc_start = self.pos - 1;
+                    let e = self.src[self.pos..].chars().next().filter(|e| *e != '\n');
+                    self.pos += e.map_or(0, |e| e.len_utf8());
+                    match e.and_then(unescape) {
+                        Some(c) => s.push(c),
+                        None => {
+                            let esc = self.src[esc_start..self.pos].to_string();
+                            self.error(
+                                format!("unknown escape sequence {} in string literal", esc),
+                                Span::new(esc_start, self.pos),
+                                LexErrorKind::BadEscape { esc },
+                            );
+                        }
+                    }
+                }
+                c => s.push(c),
+            }
+        }
+    }
+
+    /// punctuation & operators (longest match first)
+    fn lex_symbol(&mut self) -> Token {
+        const SYMBOLS: &[(&str, Token)] = &[
+            (">>>=", Token::GtGtGtEq),
+            ("[&]=", Token::IAndEq),
+            ("[|]=", Token::IOrEq),
+            (">>>", Token::GtGtGt),
+            ("[&]", Token::IAnd),
+            ("[|]", Token::IOr),
+            ("<<=", Token::LtLtEq),
+            (">>=", Token::GtGtEq),
+            ("+=", Token::PlusEq),
+            ("-=", Token::DashEq),
+            ("*=", Token::StarEq),
+            ("/=", Token::SlashEq),
+            ("%=", Token::PercentEq),
+            ("&=", Token::AmperEq),
+            ("|=", Token::BarEq),
+            ("++", Token::PlusPlus),
+            ("--", Token::DashDash),
+            ("==", Token::EqEq),
+            ("!=", Token::BangEq),
+            ("<=", Token::LtEq),
+            (">=", Token::GtEq),
+            ("<<", Token::LtLt),
+            (">>", Token::GtGt),
+            ("->", Token::Arrow),
+            (";", Token::Semi),
+            (",", Token::Comma),
+            ("{", Token::LBrace),
+            ("}", Token::RBrace),
+            ("(", Token::LParen),
+            (")", Token::RParen),
+            ("[", Token::LBracket),
+            ("]", Token::RBracket),
+            (".", Token::Dot),
+            ("?", Token::Question),
+            (":", Token::Colon),
+            ("+", Token::Plus),
+            ("-", Token::Dash),
+            ("*", Token::Star),
+            ("/", Token::Slash),
+            ("%", Token::Percent),
+            ("=", Token::Eq),
+            ("!", Token::Bang),
+            ("~", Token::Tilde),
+            ("<", Token::Lt),
+            (">", Token::Gt),
+            ("&", Token::Amper),
+            ("|", Token::Bar),
+        ];
+
+        for (sym, tok) in SYMBOLS {
+            if self.starts_with(sym) {
+                self.pos += sym.len();
+                return tok.clone();
+            }
+        }
+
+        let start = self.pos;
+        let c = self.src[self.pos..].chars().next().unwrap();
+        self.pos += c.len_utf8();
+        self.error(
+            format!("unexpected character '{}'", c.escape_default()),
+            Span::new(start, self.pos),
+            LexErrorKind::UnknownChar { ch: c },
+        );
+        Token::Error
+    }
+}
+
+/// lex a whole source file; the tokens always end with `Token::Eof`
+/// and every lexical problem is returned alongside them
+pub fn tokenize(src: &str) -> (Vec<SToken>, Vec<LexError>) {
+    let mut lexer = Lexer::new(src);
+    let mut toks = Vec::new();
+    loop {
+        let tok = lexer.next_token();
+        let done = tok.node == Token::Eof;
+        toks.push(tok);
+        if done {
+            return (toks, lexer.into_errors());
+        }
+    }
+}
+
+/// lex a whole source file keeping all trivia;
+/// concatenating the `Display` of every token gives back `src` exactly
+pub fn tokenize_lossless(src: &str) -> (Vec<LosslessToken>, Vec<LexError>) {
+    let mut lexer = Lexer::with_trivia(src);
+    let mut toks = Vec::new();
+    loop {
+        let tok = lexer.next_lossless();
+        let done = tok.token.node == Token::Eof;
+        toks.push(tok);
+        if done {
+            return (toks, lexer.into_errors());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(src: &str) -> Vec<Token> {
+        let (toks, errs) = tokenize(src);
+        assert!(errs.is_empty(), "unexpected lex errors: {:?}", errs);
+        toks.into_iter().map(|t| t.node).collect()
+    }
+
+    #[test]
+    fn test_keywords_and_idents() {
+        assert_eq!(
+            kinds("struct Point if? ifx var length_of break continue in index"),
+            vec![
+                Token::Struct,
+                Token::Ident("Point".into()),
+                Token::IfQ,
+                Token::Ident("ifx".into()),
+                Token::Var,
+                Token::Ident("length_of".into()),
+                Token::Break,
+                Token::Continue,
+                // only special in a for-each header, which the parser checks
+                Token::Ident("in".into()),
+                Token::Ident("index".into()),
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_operators_longest_match() {
+        assert_eq!(
+            kinds(">>> >> > [&] [|] [ << <= -> - != ! / % /* c */ //"),
+            vec![
+                Token::GtGtGt,
+                Token::GtGt,
+                Token::Gt,
+                Token::IAnd,
+                Token::IOr,
+                Token::LBracket,
+                Token::LtLt,
+                Token::LtEq,
+                Token::Arrow,
+                Token::Dash,
+                Token::BangEq,
+                Token::Bang,
+                Token::Slash,
+                Token::Percent,
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compound_assignment_operators() {
+        assert_eq!(
+            kinds("+= -= *= /= %= <<= >>= >>>= &= |= [&]= [|]= ++ -- >= - -"),
+            vec![
+                Token::PlusEq,
+                Token::DashEq,
+                Token::StarEq,
+                Token::SlashEq,
+                Token::PercentEq,
+                Token::LtLtEq,
+                Token::GtGtEq,
+                Token::GtGtGtEq,
+                Token::AmperEq,
+                Token::BarEq,
+                Token::IAndEq,
+                Token::IOrEq,
+                Token::PlusPlus,
+                Token::DashDash,
+                Token::GtEq,
+                Token::Dash,
+                Token::Dash,
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(
+            kinds(r#"42 true false "a\n\"b\"""#),
+            vec![
+                Token::Int(42),
+                Token::Bool(true),
+                Token::Bool(false),
+                Token::Str("a\n\"b\"".to_string()),
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extended_int_literals() {
+        assert_eq!(
+            kinds(r"0x1F 0Xff 0b1010 1_000_000 0x7fff_ffff_ffff_ffff 0xFFFF_FFFF_FFFF_FFFF"),
+            vec![
+                Token::Int(31),
+                Token::Int(255),
+                Token::Int(10),
+                Token::Int(1_000_000),
+                Token::Int(i64::MAX),
+                Token::Int(-1),
+                Token::Eof,
+            ]
+        );
+        assert_eq!(
+            kinds(r"'a' '\n' '\'' '\\' '0'"),
+            vec![
+                Token::Int(97),
+                Token::Int(10),
+                Token::Int(39),
+                Token::Int(92),
+                Token::Int(48),
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_int_literal_errors() {
+        let (toks, errs) = tokenize("0x 12ab 0b102 0x1_0000_0000_0000_0000 9223372036854775808");
+        let kinds: Vec<&LexErrorKind> = errs.iter().map(|e| &e.kind).collect();
+        assert!(matches!(kinds[0], LexErrorKind::MalformedInt { literal } if literal == "0x"));
+        assert!(matches!(kinds[1], LexErrorKind::MalformedInt { literal } if literal == "12ab"));
+        assert!(matches!(kinds[2], LexErrorKind::MalformedInt { .. }));
+        assert!(matches!(kinds[3], LexErrorKind::IntOverflow { .. }));
+        assert!(matches!(kinds[4], LexErrorKind::IntOverflow { .. }));
+        assert_eq!(errs[1].span, Span::new(3, 7));
+        assert_eq!(toks.len(), 6);
+
+        let (toks, errs) = tokenize("'' 'ab' x '\\q' 'c\n;");
+        assert_eq!(errs.len(), 4);
+        assert!(
+            errs.iter()
+                .all(|e| matches!(e.kind, LexErrorKind::BadCharLiteral))
+        );
+        assert_eq!(errs[1].span, Span::new(3, 7));
+        assert_eq!(toks[2].node, Token::Ident("x".into()));
+        assert_eq!(toks[5].node, Token::Semi);
+    }
+
+    #[test]
+    fn test_spans_skip_comments() {
+        let (toks, _) = tokenize("x /* c */ = // line\n 10;");
+        let spans: Vec<Span> = toks.iter().map(|t| t.span.clone()).collect();
+        assert_eq!(
+            spans,
+            vec![
+                Span::new(0, 1),
+                Span::new(10, 11),
+                Span::new(21, 23),
+                Span::new(23, 24),
+                Span::new(24, 24),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        // every problem is reported, lexing continues past each one
+        let src = "x = @;\ns = \"abc\ny = 99999999999999999999 + 1;";
+        let (toks, errs) = tokenize(src);
+
+        assert_eq!(errs.len(), 3);
+        assert!(matches!(
+            errs[0].kind,
+            LexErrorKind::UnknownChar { ch: '@' }
+        ));
+        assert_eq!(errs[0].span, Span::new(4, 5));
+        assert!(matches!(errs[1].kind, LexErrorKind::UnterminatedString));
+        assert!(matches!(errs[2].kind, LexErrorKind::IntOverflow { .. }));
+
+        let kinds: Vec<Token> = toks.into_iter().map(|t| t.node).collect();
+        assert_eq!(
+            kinds[kinds.len() - 5..],
+            [
+                Token::Error,
+                Token::Plus,
+                Token::Int(1),
+                Token::Semi,
+                Token::Eof
+            ]
+        );
+        assert_eq!(kinds.iter().filter(|t| **t == Token::Error).count(), 3);
+    }
+
+    #[test]
+    fn test_bad_escape_keeps_string() {
+        let (toks, errs) = tokenize(r#""a\qb" /* open"#);
+        assert_eq!(toks[0].node, Token::Str("ab".to_string()));
+        assert!(matches!(&errs[0].kind, LexErrorKind::BadEscape { esc } if esc == "\\q"));
+        assert!(matches!(errs[1].kind, LexErrorKind::UnterminatedComment));
+    }
+
+    #[test]
+    fn test_lossless_round_trip() {
+        let src = "/** doc */\nint f() {\n  return 1; // one\n}\t@ \"open\n/* tail";
+        let (toks, errs) = tokenize_lossless(src);
+        let printed: String = toks.iter().map(|t| t.to_string()).collect();
+        assert_eq!(printed, src);
+        assert_eq!(errs.len(), 3);
+
+        // same tokens and spans as the normal mode
+        let (plain, _) = tokenize(src);
+        let lossless: Vec<SToken> = toks.iter().map(|t| t.token.clone()).collect();
+        assert_eq!(lossless, plain);
+        for t in &toks {
+            assert_eq!(&src[t.token.span.start..t.token.span.end], t.text);
+        }
+    }
+
+    #[test]
+    fn test_trivia_attachment() {
+        let (toks, _) = tokenize_lossless("/* a */ x // b\n");
+        let kinds: Vec<TriviaKind> = toks[0].leading.iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![TriviaKind::BlockComment, TriviaKind::Whitespace]
+        );
+        assert_eq!(toks[0].leading[0].span, Span::new(0, 7));
+
+        // trailing trivia belongs to Eof
+        let eof = &toks[1];
+        assert_eq!(eof.token.node, Token::Eof);
+        assert_eq!(eof.leading[1].text, "// b");
+        assert_eq!(eof.leading[2].text, "\n");
+    }
+}
@@ -1,9 +1,9 @@
 //! LLVM IR
 
-pub type Uid = String;
-pub type Gid = String;
-pub type Tid = String; // type id
-pub type Lbl = String;
+pub type Uid = common::Symbol;
+pub type Gid = common::Symbol;
+pub type Tid = common::Symbol; // type id
+pub type Lbl = common::Symbol;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Ty {
@@ -36,6 +36,8 @@ pub enum Bop {
     Add,
     Sub,
     Mul,
+    SDiv, // signed, truncating
+    SRem, // signed, sign of the dividend
     Shl,
     Lshr,
     Ashr,
@@ -181,7 +183,7 @@ mod tests {
     #[test]
     fn test_block_creation() {
         let insns = vec![];
-        let term = ("ret".to_string(), Terminator::Ret(Ty::Void, None));
+        let term = ("ret".into(), Terminator::Ret(Ty::Void, None));
         let block = Block::new(insns, term);
         assert!(block.insns.is_empty());
     }
@@ -1 +1,988 @@
+//! Parser : tokens → ast::Prog
+//!
+//! hand-written recursive descent for declarations, statements and types,
+//! with precedence climbing (Pratt) for binary operators, see `BinOp::prec`.
+//! every `Node::loc` and `Spanned::span` covers the exact source text of
+//! the construct, from its first token to its last, and every `Node::id` is
+//! unique within one parse (numbered in the order the nodes are finished)
+//!
+//! syntax errors don't stop the parse: the error is recorded and the parser
+//! skips ahead to the next statement (after `;`, before `}`) or the next
+//! top-level declaration, so one run reports every independent mistake
 
+use ast::*;
+use common::{ParseError, ParseErrorKind, Span, Spanned};
+use lexer::{SToken, Token};
+
+pub type PResult<T> = Result<T, ParseError>;
+
+/// binary operator for a token, if it is one
+fn binop_of(t: &Token) -> Option<BinOp> {
+    let op = match t {
+        Token::Plus => BinOp::Add,
+        Token::Dash => BinOp::Sub,
+        Token::Star => BinOp::Mul,
+        Token::Slash => BinOp::Div,
+        Token::Percent => BinOp::Mod,
+        Token::EqEq => BinOp::Eq,
+        Token::BangEq => BinOp::Neq,
+        Token::Lt => BinOp::Lt,
+        Token::LtEq => BinOp::Lte,
+        Token::Gt => BinOp::Gt,
+        Token::GtEq => BinOp::Gte,
+        Token::Amper => BinOp::And,
+        Token::Bar => BinOp::Or,
+        Token::IAnd => BinOp::IAnd,
+        Token::IOr => BinOp::IOr,
+        Token::LtLt => BinOp::Shl,
+        Token::GtGt => BinOp::Shr,
+        Token::GtGtGt => BinOp::Sar,
+        _ => return None,
+    };
+    Some(op)
+}
+
+/// the operator of a compound assignment `lhs op= e`
+fn op_assign_of(t: &Token) -> Option<BinOp> {
+    Some(match t {
+        Token::PlusEq => BinOp::Add,
+        Token::DashEq => BinOp::Sub,
+        Token::StarEq => BinOp::Mul,
+        Token::SlashEq => BinOp::Div,
+        Token::PercentEq => BinOp::Mod,
+        Token::LtLtEq => BinOp::Shl,
+        Token::GtGtEq => BinOp::Shr,
+        Token::GtGtGtEq => BinOp::Sar,
+        Token::AmperEq => BinOp::And,
+        Token::BarEq => BinOp::Or,
+        Token::IAndEq => BinOp::IAnd,
+        Token::IOrEq => BinOp::IOr,
+        _ => return None,
+    })
+}
+
+/// unary (prefix) operator for a token, if it is one
+fn unop_of(t: &Token) -> Option<UnOp> {
+    match t {
+        Token::Dash => Some(UnOp::Neg),
+        Token::Bang => Some(UnOp::LogNot),
+        Token::Tilde => Some(UnOp::BitNot),
+        _ => None,
+    }
+}
+
+pub struct Parser {
+    toks: Vec<SToken>, // always ends with Token::Eof
+    pos: usize,
+    errors: Vec<ParseError>,
+    next_id: u32, // NodeId of the next node built
+}
+
+impl Parser {
+    pub fn new(toks: Vec<SToken>) -> Self {
+        Self {
+            toks,
+            pos: 0,
+            errors: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    pub fn into_errors(self) -> Vec<ParseError> {
+        self.errors
+    }
+
+    // --- nodes ---
+
+    fn fresh_id(&mut self) -> NodeId {
+        let id = NodeId(self.next_id);
+        self.next_id += 1;
+        id
+    }
+
+    /// build a node whose location and inner span are both `span`
+    fn node<T>(&mut self, elt: T, span: Span) -> Node<Spanned<T>> {
+        Node {
+            elt: Spanned::new(span.clone(), elt),
+            loc: span,
+            id: self.fresh_id(),
+        }
+    }
+
+    // --- token helpers ---
+
+    fn peek(&self) -> &Token {
+        self.peek_at(0)
+    }
+
+    /// n tokens ahead (sticks at Eof)
+    fn peek_at(&self, n: usize) -> &Token {
+        let i = (self.pos + n).min(self.toks.len() - 1);
+        &self.toks[i].node
+    }
+
+    fn span(&self) -> Span {
+        self.toks[self.pos].span.clone()
+    }
+
+    fn start(&self) -> usize {
+        self.toks[self.pos].span.start
+    }
+
+    /// span from `start` to the end of the last consumed token
+    fn span_from(&self, start: usize) -> Span {
+        let end = if self.pos == 0 {
+            start
+        } else {
+            self.toks[self.pos - 1].span.end
+        };
+        Span::new(start, end.max(start))
+    }
+
+    fn bump(&mut self) -> SToken {
+        let t = self.toks[self.pos].clone();
+        if self.pos < self.toks.len() - 1 {
+            self.pos += 1;
+        }
+        t
+    }
+
+    fn eat(&mut self, t: &Token) -> bool {
+        if self.peek() == t {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> ParseError {
+        let found = self.peek().to_string();
+        ParseError::new(
+            format!("expected {}, found `{}`", expected, found),
+            self.span(),
+            ParseErrorKind::Unexpected {
+                expected: expected.to_string(),
+                found,
+            },
+        )
+    }
+
+    fn expect(&mut self, t: Token) -> PResult<Span> {
+        if *self.peek() == t {
+            Ok(self.bump().span)
+        } else {
+            Err(self.unexpected(&format!("`{}`", t)))
+        }
+    }
+
+    fn expect_ident(&mut self) -> PResult<IdTy> {
+        match self.peek() {
+            Token::Ident(id) => {
+                let id = *id;
+                self.bump();
+                Ok(id)
+            }
+            _ => Err(self.unexpected("identifier")),
+        }
+    }
+
+    /// parse `item (, item)*` up to (and including) `close`
+    fn comma_list<T>(
+        &mut self,
+        close: Token,
+        mut item: impl FnMut(&mut Self) -> PResult<T>,
+    ) -> PResult<Vec<T>> {
+        let mut items = Vec::new();
+        if self.eat(&close) {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if !self.eat(&Token::Comma) {
+                break;
+            }
+        }
+        self.expect(close)?;
+        Ok(items)
+    }
+
+    // --- error recovery ---
+
+    /// record a syntax error; errors at an `Error` token were already reported
+    /// by the lexer, and a second error at the same spot is only a cascade
+    fn report(&mut self, e: ParseError) {
+        if *self.peek() == Token::Error {
+            return;
+        }
+        if self.errors.last().is_some_and(|last| last.span == e.span) {
+            return;
+        }
+        self.errors.push(e);
+    }
+
+    /// skip to the start of the next statement: just past a `;` or a
+    /// `{ .. }` group, or just before a `}` closing the enclosing block
+    fn sync_stmt(&mut self, start: usize) {
+        let mut depth = 0usize;
+        loop {
+            match self.peek() {
+                Token::Eof => return,
+                Token::RBrace if depth == 0 => break,
+                Token::Semi if depth == 0 => {
+                    self.bump();
+                    break;
+                }
+                Token::LBrace => depth += 1,
+                Token::RBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.bump();
+                        break;
+                    }
+                }
+                Token::If
+                | Token::IfQ
+                | Token::While
+                | Token::For
+                | Token::Return
+                | Token::Break
+                | Token::Continue
+                | Token::Var
+                    if depth == 0 && self.pos > start =>
+                {
+                    break;
+                }
+                _ => {}
+            }
+            self.bump();
+        }
+        // always make progress
+        if self.pos == start {
+            self.bump();
+        }
+    }
+
+    /// does a top-level declaration start here?
+    fn at_decl_start(&self) -> bool {
+        match self.peek() {
+            Token::Struct | Token::Global => true,
+            Token::Void => {
+                matches!(self.peek_at(1), Token::Ident(_)) && *self.peek_at(2) == Token::LParen
+            }
+            _ => self.skip_ty(self.pos).is_some_and(|j| {
+                matches!(self.toks[j.min(self.toks.len() - 1)].node, Token::Ident(_))
+                    && self.toks[(j + 1).min(self.toks.len() - 1)].node == Token::LParen
+            }),
+        }
+    }
+
+    /// skip to the next top-level declaration (outside any braces)
+    fn sync_decl(&mut self, start: usize) {
+        if self.pos == start {
+            self.bump();
+        }
+        let mut depth = 0usize;
+        loop {
+            match self.peek() {
+                Token::Eof => return,
+                Token::LBrace => depth += 1,
+                Token::RBrace => depth = depth.saturating_sub(1),
+                _ if depth == 0 && self.at_decl_start() => return,
+                _ => {}
+            }
+            self.bump();
+        }
+    }
+
+    // --- types ---
+
+    /// index just past the type starting at token `i`, if there is one
+    /// (pure lookahead: used to tell declarations and `T null` apart from expressions)
+    fn skip_ty(&self, mut i: usize) -> Option<usize> {
+        let tok = |i: usize| &self.toks[i.min(self.toks.len() - 1)].node;
+        match tok(i) {
+            Token::TInt | Token::TBool | Token::TString | Token::Ident(_) => i += 1,
+            Token::LParen => {
+                i += 1;
+                let mut n = 0;
+                if *tok(i) != Token::RParen {
+                    loop {
+                        i = self.skip_ty(i)?;
+                        n += 1;
+                        if *tok(i) != Token::Comma {
+                            break;
+                        }
+                        i += 1;
+                    }
+                }
+                if *tok(i) != Token::RParen {
+                    return None;
+                }
+                i += 1;
+                if *tok(i) == Token::Arrow {
+                    i += 1;
+                    i = if *tok(i) == Token::Void {
+                        i + 1
+                    } else {
+                        self.skip_ty(i)?
+                    };
+                } else if n != 1 {
+                    return None;
+                }
+            }
+            _ => return None,
+        }
+        loop {
+            match (tok(i), tok(i + 1)) {
+                (Token::LBracket, Token::RBracket) => i += 2,
+                (Token::Question, _) => i += 1,
+                _ => return Some(i),
+            }
+        }
+    }
+
+    /// ty: int | bool | string | Id | (ty, ..) -> ret | (ty) | ty[] | ty?
+    pub fn parse_ty(&mut self) -> PResult<STy> {
+        let start = self.start();
+        let base = match self.peek() {
+            Token::TInt => {
+                self.bump();
+                Ty::TInt
+            }
+            Token::TBool => {
+                self.bump();
+                Ty::TBool
+            }
+            Token::TString => {
+                let span = self.bump().span;
+                Ty::TRef(Spanned::new(span, RefTy::RString))
+            }
+            Token::Ident(id) => {
+                let id = *id;
+                let span = self.bump().span;
+                Ty::TRef(Spanned::new(span, RefTy::RStruct(id)))
+            }
+            // a function type, or a type in parentheses: `((int) -> int)[]`
+            Token::LParen => {
+                self.bump();
+                let mut args = self.comma_list(Token::RParen, |p| p.parse_ty())?;
+                if args.len() == 1 && *self.peek() != Token::Arrow {
+                    args.pop().unwrap().node
+                } else {
+                    self.expect(Token::Arrow)?;
+                    let ret = self.parse_ret_ty()?;
+                    let fun = RefTy::RFun(args, Box::new(ret));
+                    Ty::TRef(Spanned::new(self.span_from(start), fun))
+                }
+            }
+            _ => return Err(self.unexpected("type")),
+        };
+
+        let mut ty = Spanned::new(self.span_from(start), base);
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Token::LBracket, Token::RBracket) => {
+                    self.bump();
+                    self.bump();
+                    let span = self.span_from(start);
+                    let arr = Spanned::new(span.clone(), RefTy::RArray(Box::new(ty)));
+                    ty = Spanned::new(span, Ty::TRef(arr));
+                }
+                (Token::Question, _) => match ty.node {
+                    Ty::TRef(r) => {
+                        self.bump();
+                        ty = Spanned::new(self.span_from(start), Ty::TNullRef(r));
+                    }
+                    _ => return Err(self.unexpected("`[]` (only reference types are nullable)")),
+                },
+                _ => return Ok(ty),
+            }
+        }
+    }
+
+    /// ret_ty: void | ty
+    fn parse_ret_ty(&mut self) -> PResult<SRetTy> {
+        if *self.peek() == Token::Void {
+            let span = self.bump().span;
+            return Ok(Spanned::new(span, RetTy::RetVoid));
+        }
+        let ty = self.parse_ty()?;
+        Ok(Spanned::new(ty.span.clone(), RetTy::RetVal(Box::new(ty))))
+    }
+
+    // --- expressions ---
+
+    /// `guard ? e1 : e2` below all binary operators, nesting to the right
+    pub fn parse_exp(&mut self) -> PResult<Node<SExp>> {
+        let start = self.start();
+        let guard = self.parse_bin(0)?;
+        if !self.eat(&Token::Question) {
+            return Ok(guard);
+        }
+        let e1 = self.parse_exp()?;
+        self.expect(Token::Colon)?;
+        let e2 = self.parse_exp()?;
+        Ok(self.node(
+            Exp::Cond(Box::new(guard), Box::new(e1), Box::new(e2)),
+            self.span_from(start),
+        ))
+    }
+
+    /// precedence climbing: operators binding at least as tight as `min_prec`
+    fn parse_bin(&mut self, min_prec: u8) -> PResult<Node<SExp>> {
+        let start = self.start();
+        let mut lhs = self.parse_unary()?;
+        while let Some(op) = binop_of(self.peek()) {
+            let prec = op.prec();
+            if prec < min_prec {
+                break;
+            }
+            self.bump();
+            let rhs = self.parse_bin(prec + 1)?;
+            lhs = self.node(
+                Exp::Bop(op, Box::new(lhs), Box::new(rhs)),
+                self.span_from(start),
+            );
+        }
+        Ok(lhs)
+    }
+
+    /// prefix operators bind looser than postfix ones: `-a[i]` is `-(a[i])`
+    fn parse_unary(&mut self) -> PResult<Node<SExp>> {
+        match unop_of(self.peek()) {
+            Some(op) => {
+                let start = self.start();
+                self.bump();
+                let e = self.parse_unary()?;
+                Ok(self.node(Exp::Uop(op, Box::new(e)), self.span_from(start)))
+            }
+            None => self.parse_postfix(),
+        }
+    }
+
+    /// indexing `e[i]`, calls `e(args)` and projections `e.f`
+    fn parse_postfix(&mut self) -> PResult<Node<SExp>> {
+        let start = self.start();
+        let mut e = self.parse_primary()?;
+        loop {
+            match self.peek() {
+                Token::LBracket => {
+                    self.bump();
+                    let i = self.parse_exp()?;
+                    self.expect(Token::RBracket)?;
+                    e = self.node(Exp::Index(Box::new(e), Box::new(i)), self.span_from(start));
+                }
+                Token::LParen => {
+                    self.bump();
+                    let args = self.comma_list(Token::RParen, |p| p.parse_exp())?;
+                    e = self.node(Exp::Call(Box::new(e), args), self.span_from(start));
+                }
+                Token::Dot => {
+                    self.bump();
+                    let field = self.expect_ident()?;
+                    e = self.node(Exp::Proj(Box::new(e), field), self.span_from(start));
+                }
+                _ => return Ok(e),
+            }
+        }
+    }
+
+    fn parse_primary(&mut self) -> PResult<Node<SExp>> {
+        let start = self.start();
+
+        // `rty null`, e.g. `string null`, `int[] null`, `Point null`
+        if let Some(j) = self.skip_ty(self.pos)
+            && self.toks[j.min(self.toks.len() - 1)].node == Token::Null
+        {
+            let ty = self.parse_ty()?;
+            let r = match ty.node {
+                Ty::TRef(r) | Ty::TNullRef(r) => r,
+                _ => return Err(self.unexpected("reference type before `null`")),
+            };
+            self.expect(Token::Null)?;
+            return Ok(self.node(Exp::CNull(r), self.span_from(start)));
+        }
+
+        let e = match self.peek().clone() {
+            Token::Int(i) => {
+                self.bump();
+                Exp::CInt(i)
+            }
+            Token::Str(s) => {
+                self.bump();
+                Exp::CStr(s)
+            }
+            Token::Bool(b) => {
+                self.bump();
+                Exp::CBool(b)
+            }
+            Token::Ident(id) => {
+                self.bump();
+                Exp::Id(id)
+            }
+            Token::New => return self.parse_new(),
+            Token::Length => {
+                self.bump();
+                self.expect(Token::LParen)?;
+                let e = self.parse_exp()?;
+                self.expect(Token::RParen)?;
+                Exp::Length(Box::new(e))
+            }
+            Token::LParen => {
+                self.bump();
+                let e = self.parse_exp()?;
+                self.expect(Token::RParen)?;
+                return Ok(e);
+            }
+            _ => return Err(self.unexpected("expression")),
+        };
+        Ok(self.node(e, self.span_from(start)))
+    }
+
+    /// new ty[e]  |  new ty[]{e, ..}  |  new S { f = e; .. }
+    fn parse_new(&mut self) -> PResult<Node<SExp>> {
+        let start = self.start();
+        self.expect(Token::New)?;
+
+        if let (Token::Ident(id), Token::LBrace) = (self.peek(), self.peek_at(1)) {
+            let id = *id;
+            self.bump();
+            self.bump();
+            let mut fields = Vec::new();
+            while !matches!(self.peek(), Token::RBrace | Token::Eof) {
+                let cf_id = self.expect_ident()?;
+                self.expect(Token::Eq)?;
+                let cf_node = self.parse_exp()?;
+                fields.push(CField { cf_id, cf_node });
+                if !self.eat(&Token::Semi) {
+                    break;
+                }
+            }
+            self.expect(Token::RBrace)?;
+            return Ok(self.node(Exp::CStruct(id, fields), self.span_from(start)));
+        }
+
+        // `[]` pairs are part of the type, so `new int[][3]` is an array of int[]
+        let ty = self.parse_ty()?;
+
+        match self.peek() {
+            Token::LBracket => {
+                self.bump();
+                let size = self.parse_exp()?;
+                self.expect(Token::RBracket)?;
+                Ok(self.node(Exp::NewArr(ty.node, Box::new(size)), self.span_from(start)))
+            }
+            Token::LBrace => {
+                let elt = match ty.node {
+                    Ty::TRef(Spanned {
+                        node: RefTy::RArray(elt),
+                        ..
+                    }) => elt.node,
+                    _ => return Err(self.unexpected("`[` after the type of `new`")),
+                };
+                self.bump();
+                let elems = self.comma_list(Token::RBrace, |p| p.parse_exp())?;
+                Ok(self.node(Exp::CArr(elt, elems), self.span_from(start)))
+            }
+            _ => Err(self.unexpected("`[` or `{`")),
+        }
+    }
+
+    // --- statements ---
+
+    /// does a typed declaration `ty x = e` / `ty x` start here?
+    fn at_typed_vdecl(&self) -> bool {
+        let tok = |i: usize| &self.toks[i.min(self.toks.len() - 1)].node;
+        self.skip_ty(self.pos).is_some_and(|j| {
+            matches!(tok(j), Token::Ident(_))
+                && matches!(
+                    tok(j + 1),
+                    Token::Eq | Token::Semi | Token::Comma | Token::RParen
+                )
+        })
+    }
+
+    /// var x = e  |  ty x = e  |  ty x
+    fn parse_vdecl(&mut self) -> PResult<VDecl> {
+        if self.eat(&Token::Var) {
+            let vd_id = self.expect_ident()?;
+            self.expect(Token::Eq)?;
+            let init = self.parse_exp()?;
+            return Ok(VDecl {
+                vd_id,
+                vd_ty: None,
+                vd_node: Some(init),
+            });
+        }
+
+        let ty = self.parse_ty()?;
+        let vd_id = self.expect_ident()?;
+        let vd_node = if self.eat(&Token::Eq) {
+            Some(self.parse_exp()?)
+        } else {
+            None
+        };
+        Ok(VDecl {
+            vd_id,
+            vd_ty: Some(ty),
+            vd_node,
+        })
+    }
+
+    /// `lhs = e`, `lhs op= e`, `lhs++`, `lhs--`, `f(args)` or a declaration,
+    /// without the trailing `;`
+    fn parse_simple_stmt(&mut self) -> PResult<Node<SStmt>> {
+        let start = self.start();
+        if *self.peek() == Token::Var || self.at_typed_vdecl() {
+            let vd = self.parse_vdecl()?;
+            return Ok(self.node(Stmt::Decl(vd), self.span_from(start)));
+        }
+
+        let e = self.parse_exp()?;
+        if self.eat(&Token::Eq) {
+            let rhs = self.parse_exp()?;
+            return Ok(self.node(Stmt::Assn(e, rhs), self.span_from(start)));
+        }
+        if let Some(op) = op_assign_of(self.peek()) {
+            self.bump();
+            let rhs = self.parse_exp()?;
+            return Ok(self.node(Stmt::OpAssn(e, op, rhs), self.span_from(start)));
+        }
+        if self.eat(&Token::PlusPlus) {
+            return Ok(self.node(Stmt::Incr(e), self.span_from(start)));
+        }
+        if self.eat(&Token::DashDash) {
+            return Ok(self.node(Stmt::Decr(e), self.span_from(start)));
+        }
+        match e.elt.node {
+            Exp::Call(f, args) => Ok(self.node(Stmt::SCall(*f, args), self.span_from(start))),
+            _ => Err(self.unexpected("`=` or a call")),
+        }
+    }
+
+    pub fn parse_stmt(&mut self) -> PResult<Node<SStmt>> {
+        let start = self.start();
+        match self.peek() {
+            Token::If => self.parse_if(),
+            Token::IfQ => self.parse_if_q(),
+            Token::Return => {
+                self.bump();
+                let e = if *self.peek() == Token::Semi {
+                    None
+                } else {
+                    Some(self.parse_exp()?)
+                };
+                self.expect(Token::Semi)?;
+                Ok(self.node(Stmt::Ret(e), self.span_from(start)))
+            }
+            Token::While => {
+                self.bump();
+                self.expect(Token::LParen)?;
+                let guard = self.parse_exp()?;
+                self.expect(Token::RParen)?;
+                let body = self.parse_block()?;
+                Ok(self.node(Stmt::While(guard, body), self.span_from(start)))
+            }
+            Token::For => self.parse_for(),
+            Token::Break | Token::Continue => {
+                let s = if self.bump().node == Token::Break {
+                    Stmt::Break
+                } else {
+                    Stmt::Continue
+                };
+                self.expect(Token::Semi)?;
+                Ok(self.node(s, self.span_from(start)))
+            }
+            _ => {
+                let mut s = self.parse_simple_stmt()?;
+                self.expect(Token::Semi)?;
+                s.loc = self.span_from(start);
+                s.elt.span = s.loc.clone();
+                Ok(s)
+            }
+        }
+    }
+
+    /// if (e) { .. } [else { .. } | else if ..]
+    fn parse_if(&mut self) -> PResult<Node<SStmt>> {
+        let start = self.start();
+        self.expect(Token::If)?;
+        self.expect(Token::LParen)?;
+        let guard = self.parse_exp()?;
+        self.expect(Token::RParen)?;
+        let then_block = self.parse_block()?;
+        let else_block = self.parse_else()?;
+        Ok(self.node(
+            Stmt::If(guard, then_block, else_block),
+            self.span_from(start),
+        ))
+    }
+
+    /// if? (r x = e) { .. } [else ..], where r is a reference type
+    fn parse_if_q(&mut self) -> PResult<Node<SStmt>> {
+        let start = self.start();
+        self.expect(Token::IfQ)?;
+        self.expect(Token::LParen)?;
+        let ty = self.parse_ty()?;
+        let r = match ty.node {
+            Ty::TRef(r) => r,
+            t => {
+                return Err(ParseError::new(
+                    format!("expected reference type, found `{}`", t),
+                    ty.span,
+                    ParseErrorKind::Unexpected {
+                        expected: "reference type".to_string(),
+                        found: t.to_string(),
+                    },
+                ));
+            }
+        };
+        let x = self.expect_ident()?;
+        self.expect(Token::Eq)?;
+        let e = self.parse_exp()?;
+        self.expect(Token::RParen)?;
+        let then_block = self.parse_block()?;
+        let else_block = self.parse_else()?;
+        Ok(self.node(
+            Stmt::Cast(r, x, e, then_block, else_block),
+            self.span_from(start),
+        ))
+    }
+
+    /// [else { .. } | else if .. | else if? ..]
+    fn parse_else(&mut self) -> PResult<Block> {
+        if !self.eat(&Token::Else) {
+            return Ok(vec![]);
+        }
+        match self.peek() {
+            Token::If => Ok(vec![self.parse_if()?]),
+            Token::IfQ => Ok(vec![self.parse_if_q()?]),
+            _ => self.parse_block(),
+        }
+    }
+
+    /// for (var i = 0, int j, ..; guard; step) { .. }  (step may end with `;`)
+    /// | for (x in e) { .. }
+    fn parse_for(&mut self) -> PResult<Node<SStmt>> {
+        let start = self.start();
+        self.expect(Token::For)?;
+        self.expect(Token::LParen)?;
+
+        // `in` is not a keyword: `x in` can only start a for-each header here
+        if let (Token::Ident(x), Token::Ident(kw)) = (self.peek(), self.peek_at(1))
+            && *kw == "in"
+        {
+            let x = *x;
+            self.bump();
+            self.bump();
+            let arr = self.parse_exp()?;
+            self.expect(Token::RParen)?;
+            let body = self.parse_block()?;
+            return Ok(self.node(Stmt::ForEach(x, arr, body), self.span_from(start)));
+        }
+
+        let mut vdecls = Vec::new();
+        if *self.peek() != Token::Semi {
+            loop {
+                vdecls.push(self.parse_vdecl()?);
+                if !self.eat(&Token::Comma) {
+                    break;
+                }
+            }
+        }
+        self.expect(Token::Semi)?;
+
+        let guard = if *self.peek() == Token::Semi {
+            None
+        } else {
+            Some(self.parse_exp()?)
+        };
+        self.expect(Token::Semi)?;
+
+        let step = if *self.peek() == Token::RParen {
+            None
+        } else {
+            let s = self.parse_simple_stmt()?;
+            self.eat(&Token::Semi);
+            Some(Box::new(s))
+        };
+        self.expect(Token::RParen)?;
+
+        let body = self.parse_block()?;
+        Ok(self.node(Stmt::For(vdecls, guard, step, body), self.span_from(start)))
+    }
+
+    /// `{ stmt* }`; a statement with a syntax error is reported and dropped,
+    /// and a missing `}` is reported without losing the statements
+    pub fn parse_block(&mut self) -> PResult<Block> {
+        self.expect(Token::LBrace)?;
+        let mut stmts = Vec::new();
+        while !matches!(self.peek(), Token::RBrace | Token::Eof) {
+            // a declaration keyword means this block was never closed
+            if matches!(self.peek(), Token::Struct | Token::Global) {
+                break;
+            }
+            let start = self.pos;
+            match self.parse_stmt() {
+                Ok(s) => stmts.push(s),
+                Err(e) => {
+                    self.report(e);
+                    self.sync_stmt(start);
+                }
+            }
+        }
+        if let Err(e) = self.expect(Token::RBrace) {
+            self.report(e);
+        }
+        Ok(stmts)
+    }
+
+    // --- declarations ---
+
+    pub fn parse_decl(&mut self) -> PResult<Decl> {
+        let start = self.start();
+        match self.peek() {
+            // global x = e;
+            Token::Global => {
+                self.bump();
+                let name = self.expect_ident()?;
+                self.expect(Token::Eq)?;
+                let init = self.parse_exp()?;
+                self.expect(Token::Semi)?;
+                Ok(Decl::GVDecl(Node {
+                    elt: GDecl { name, init },
+                    loc: self.span_from(start),
+                    id: self.fresh_id(),
+                }))
+            }
+
+            // struct S { ty f; .. }
+            Token::Struct => {
+                self.bump();
+                let td_id = self.expect_ident()?;
+                self.expect(Token::LBrace)?;
+                let mut fields = Vec::new();
+                while !matches!(self.peek(), Token::RBrace | Token::Eof) {
+                    let field_type = self.parse_ty()?.node;
+                    let field_name = self.expect_ident()?;
+                    fields.push(Field {
+                        field_name,
+                        field_type,
+                    });
+                    if !self.eat(&Token::Semi) {
+                        break;
+                    }
+                }
+                self.expect(Token::RBrace)?;
+                Ok(Decl::GTDecl(Node {
+                    elt: TDecl {
+                        td_id,
+                        td_node: fields,
+                    },
+                    loc: self.span_from(start),
+                    id: self.fresh_id(),
+                }))
+            }
+
+            // ret_ty f(ty x, ..) { .. }
+            _ => {
+                let fret_ty = self.parse_ret_ty()?.node;
+                let fname = self.expect_ident()?;
+                self.expect(Token::LParen)?;
+                let args = self.comma_list(Token::RParen, |p| {
+                    let ty = p.parse_ty()?;
+                    let id = p.expect_ident()?;
+                    Ok(Arg { ty, id })
+                })?;
+                let body = self.parse_block()?;
+                Ok(Decl::GFDecl(Node {
+                    elt: FDecl {
+                        fret_ty,
+                        fname,
+                        args,
+                        body,
+                    },
+                    loc: self.span_from(start),
+                    id: self.fresh_id(),
+                }))
+            }
+        }
+    }
+
+    /// every declaration that parsed; the errors are left in `self.errors`
+    pub fn parse_prog(&mut self) -> Prog {
+        let mut prog = Vec::new();
+        while *self.peek() != Token::Eof {
+            let start = self.pos;
+            match self.parse_decl() {
+                Ok(d) => prog.push(d),
+                Err(e) => {
+                    self.report(e);
+                    self.sync_decl(start);
+                }
+            }
+        }
+        prog
+    }
+}
+
+/// lex `src` and run `f` on the tokens, collecting every error in source order
+fn run<T>(src: &str, f: impl FnOnce(&mut Parser) -> T) -> (T, Vec<ParseError>) {
+    let (toks, lex_errs) = lexer::tokenize(src);
+    let mut parser = Parser::new(toks);
+    let res = f(&mut parser);
+
+    let mut errors: Vec<ParseError> = lex_errs.into_iter().map(ParseError::from).collect();
+    errors.extend(parser.into_errors());
+    errors.sort_by_key(|e| e.span.start);
+    (res, errors)
+}
+
+/// parse `src` as exactly one fragment (nothing may follow it)
+fn fragment<T>(src: &str, f: impl FnOnce(&mut Parser) -> PResult<T>) -> Result<T, Vec<ParseError>> {
+    let (res, errors) = run(src, |p| {
+        let res = f(p);
+        match res {
+            Ok(_) if *p.peek() != Token::Eof => {
+                let e = p.unexpected("end of input");
+                p.report(e);
+                None
+            }
+            Ok(t) => Some(t),
+            Err(e) => {
+                p.report(e);
+                None
+            }
+        }
+    });
+    match res {
+        Some(t) if errors.is_empty() => Ok(t),
+        _ => Err(errors),
+    }
+}
+
+/// lex and parse a whole Oat program, returning the (possibly partial)
+/// program along with every lexical and syntax error, in source order
+pub fn parse_prog(src: &str) -> (Prog, Vec<ParseError>) {
+    run(src, |p| p.parse_prog())
+}
+
+/// parse a single expression, e.g. `parse_exp("a[i] + 1")`
+pub fn parse_exp(src: &str) -> Result<Node<SExp>, Vec<ParseError>> {
+    fragment(src, |p| p.parse_exp())
+}
+
+/// parse a single statement, including its `;` (or block)
+pub fn parse_stmt(src: &str) -> Result<Node<SStmt>, Vec<ParseError>> {
+    fragment(src, |p| p.parse_stmt())
+}
+
+/// parse a type, e.g. `parse_ty("(int, string) -> bool[]?")`
+pub fn parse_ty(src: &str) -> Result<STy, Vec<ParseError>> {
+    fragment(src, |p| p.parse_ty())
+}
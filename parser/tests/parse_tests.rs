@@ -0,0 +1,630 @@
+// Parser tests: precedence, statement/declaration forms and source spans
+
+use ast::*;
+use common::{ParseError, ParseErrorKind, Span};
+
+// parse a program that must be free of errors
+fn parse_prog(src: &str) -> Result<Prog, Vec<ParseError>> {
+    let (prog, errs) = parser::parse_prog(src);
+    if errs.is_empty() { Ok(prog) } else { Err(errs) }
+}
+
+// spans of the errors reported for `src`, rendered as source text
+fn error_texts<'a>(src: &'a str, errs: &[ParseError]) -> Vec<&'a str> {
+    errs.iter()
+        .map(|e| &src[e.span.start..e.span.end])
+        .collect()
+}
+
+// body of `void f() { <src> }`
+fn body(src: &str) -> Block {
+    let prog = parse_prog(&format!("void f() {{ {} }}", src)).expect("parse failed");
+    match prog.into_iter().next() {
+        Some(Decl::GFDecl(f)) => f.elt.body,
+        d => panic!("expected a function, got {:?}", d),
+    }
+}
+
+// an expression that must parse
+fn exp(src: &str) -> Node<SExp> {
+    parser::parse_exp(src).expect("parse failed")
+}
+
+// fully parenthesized rendering, to check tree shape
+fn shape(e: &Node<SExp>) -> String {
+    match &e.elt.node {
+        Exp::CInt(i) => i.to_string(),
+        Exp::CBool(b) => b.to_string(),
+        Exp::Id(x) => x.to_string(),
+        Exp::Bop(op, l, r) => format!("({} {:?} {})", shape(l), op, shape(r)),
+        Exp::Uop(op, e) => format!("({:?} {})", op, shape(e)),
+        Exp::Index(a, i) => format!("{}[{}]", shape(a), shape(i)),
+        Exp::Proj(e, f) => format!("{}.{}", shape(e), f),
+        Exp::Length(e) => format!("length({})", shape(e)),
+        Exp::Call(f, args) => {
+            let args: Vec<_> = args.iter().map(shape).collect();
+            format!("{}({})", shape(f), args.join(", "))
+        }
+        Exp::Cond(g, e1, e2) => format!("({} ? {} : {})", shape(g), shape(e1), shape(e2)),
+        e => format!("{:?}", e),
+    }
+}
+
+#[test]
+fn test_precedence() {
+    assert_eq!(shape(&exp("1 + 2 * 3")), "(1 Add (2 Mul 3))");
+    assert_eq!(shape(&exp("1 - 2 - 3")), "((1 Sub 2) Sub 3)");
+    assert_eq!(shape(&exp("a | b & c")), "(a Or (b And c))");
+    assert_eq!(shape(&exp("a [|] b [&] c")), "(a IOr (b IAnd c))");
+    assert_eq!(shape(&exp("a | b [&] c")), "((a Or b) IAnd c)");
+    assert_eq!(shape(&exp("a [|] b & c")), "(a IOr (b And c))");
+    assert_eq!(shape(&exp("a [&] b | c & d")), "(a IAnd (b Or (c And d)))");
+    assert_eq!(shape(&exp("a & b [|] c | d")), "((a And b) IOr (c Or d))");
+    assert_eq!(shape(&exp("1 << 2 + 3")), "(1 Shl (2 Add 3))");
+    assert_eq!(shape(&exp("x < 1 == y >= 2")), "((x Lt 1) Eq (y Gte 2))");
+    assert_eq!(shape(&exp("a & b == c")), "(a And (b Eq c))");
+    assert_eq!(shape(&exp("(1 + 2) * 3")), "((1 Add 2) Mul 3)");
+    assert_eq!(shape(&exp("a - b / c % d")), "(a Sub ((b Div c) Mod d))");
+    assert_eq!(shape(&exp("a * b / c")), "((a Mul b) Div c)");
+}
+
+#[test]
+fn test_conditional() {
+    assert_eq!(
+        shape(&exp("a | b ? 1 + 2 : 3")),
+        "((a Or b) ? (1 Add 2) : 3)"
+    );
+    assert_eq!(shape(&exp("a ? b : c ? d : e")), "(a ? b : (c ? d : e))");
+    assert_eq!(shape(&exp("a ? b ? c : d : e")), "(a ? (b ? c : d) : e)");
+    assert_eq!(shape(&exp("(a ? b : c) + 1")), "((a ? b : c) Add 1)");
+    assert_eq!(shape(&exp("f(a ? b : c)[0]")), "f((a ? b : c))[0]");
+
+    // a nullable type before an identifier is still a declaration
+    let b = body("S? x = S null; x = a ? x : y;");
+    assert!(matches!(b[0].elt.node, Stmt::Decl(_)));
+    assert!(matches!(&b[1].elt.node, Stmt::Assn(_, e) if matches!(e.elt.node, Exp::Cond(..))));
+
+    assert!(parser::parse_exp("a ? b").is_err());
+}
+
+#[test]
+fn test_unary_and_postfix() {
+    assert_eq!(shape(&exp("-a[1]")), "(Neg a[1])");
+    assert_eq!(
+        shape(&exp("!f(x, 2) & ~y")),
+        "((LogNot f(x, 2)) And (BitNot y))"
+    );
+    assert_eq!(shape(&exp("- -1 * 2")), "((Neg (Neg 1)) Mul 2)");
+    assert_eq!(shape(&exp("a[1][2]")), "a[1][2]");
+    assert_eq!(shape(&exp("length(a[0]) - 1")), "(length(a[0]) Sub 1)");
+    assert_eq!(shape(&exp("length(xs)[0]")), "length(xs)[0]");
+}
+
+#[test]
+fn test_literals_and_new() {
+    assert_eq!(exp("\"hi\"").elt.node, Exp::CStr("hi".to_string()));
+    assert_eq!(exp("true").elt.node, Exp::CBool(true));
+
+    match exp("new int[3]").elt.node {
+        Exp::NewArr(Ty::TInt, n) => assert_eq!(n.elt.node, Exp::CInt(3)),
+        e => panic!("got {:?}", e),
+    }
+    match exp("new bool[]{true, false}").elt.node {
+        Exp::CArr(Ty::TBool, es) => assert_eq!(es.len(), 2),
+        e => panic!("got {:?}", e),
+    }
+    // array of arrays
+    match exp("new int[][2]").elt.node {
+        Exp::NewArr(Ty::TRef(r), _) => match r.node {
+            RefTy::RArray(t) => assert_eq!(t.node, Ty::TInt),
+            r => panic!("got {:?}", r),
+        },
+        e => panic!("got {:?}", e),
+    }
+}
+
+#[test]
+fn test_null_literals() {
+    match exp("string null").elt.node {
+        Exp::CNull(r) => assert_eq!(r.node, RefTy::RString),
+        e => panic!("got {:?}", e),
+    }
+    match exp("int[] null").elt.node {
+        Exp::CNull(r) => assert!(matches!(r.node, RefTy::RArray(_))),
+        e => panic!("got {:?}", e),
+    }
+    match exp("Point null").elt.node {
+        Exp::CNull(r) => assert_eq!(r.node, RefTy::RStruct("Point".into())),
+        e => panic!("got {:?}", e),
+    }
+}
+
+#[test]
+fn test_struct_literals_and_projection() {
+    match exp("new P { x = 1; y = a.b }").elt.node {
+        Exp::CStruct(id, fields) => {
+            assert_eq!(id, "P");
+            assert_eq!(fields.len(), 2);
+            assert_eq!(fields[0].cf_id, "x");
+            assert_eq!(shape(&fields[1].cf_node), "a.b");
+        }
+        e => panic!("got {:?}", e),
+    }
+    // a trailing `;` is allowed, and so is no field at all
+    assert!(matches!(exp("new P { x = 1; }").elt.node, Exp::CStruct(_, f) if f.len() == 1));
+    assert!(matches!(exp("new P { }").elt.node, Exp::CStruct(_, f) if f.is_empty()));
+
+    assert_eq!(shape(&exp("-p.x + q.r.s")), "((Neg p.x) Add q.r.s)");
+    assert_eq!(shape(&exp("f(1).xs[2].y")), "f(1).xs[2].y");
+
+    let b = body("p.x = 3;");
+    match &b[0].elt.node {
+        Stmt::Assn(lhs, _) => assert_eq!(shape(lhs), "p.x"),
+        s => panic!("got {:?}", s),
+    }
+}
+
+#[test]
+fn test_statements() {
+    let b = body(
+        "var x = 1; x = x + 1; print(x); \
+         if (x > 1) { return; } else if (x < 0) { x = 0; } else { x = 1; } \
+         while (x > 0) { x = x - 1; } \
+         for (var i = 0, var j = 1; i < 10; i = i + 1;) { } \
+         for (;;) { break; continue; }",
+    );
+    assert_eq!(b.len(), 7);
+    assert!(matches!(b[0].elt.node, Stmt::Decl(_)));
+    assert!(matches!(b[1].elt.node, Stmt::Assn(_, _)));
+    assert!(matches!(b[2].elt.node, Stmt::SCall(_, _)));
+    match &b[3].elt.node {
+        Stmt::If(_, then, els) => {
+            assert!(matches!(then[0].elt.node, Stmt::Ret(None)));
+            assert!(matches!(els[0].elt.node, Stmt::If(_, _, _)));
+        }
+        s => panic!("got {:?}", s),
+    }
+    assert!(matches!(b[4].elt.node, Stmt::While(_, _)));
+    match &b[5].elt.node {
+        Stmt::For(vds, Some(_), Some(_), _) => assert_eq!(vds.len(), 2),
+        s => panic!("got {:?}", s),
+    }
+    match &b[6].elt.node {
+        Stmt::For(_, None, None, body) => {
+            assert!(matches!(body[0].elt.node, Stmt::Break));
+            assert!(matches!(body[1].elt.node, Stmt::Continue));
+        }
+        s => panic!("got {:?}", s),
+    }
+}
+
+#[test]
+fn test_compound_assignment() {
+    let b = body("x += 1; a[i] >>>= 2; p.ok &= b; x[|]= 4; x++; a[0]--;");
+    let ops: Vec<_> = b
+        .iter()
+        .map(|s| match &s.elt.node {
+            Stmt::OpAssn(lhs, op, _) => format!("{} {:?}", shape(lhs), op),
+            Stmt::Incr(lhs) => format!("{}++", shape(lhs)),
+            Stmt::Decr(lhs) => format!("{}--", shape(lhs)),
+            s => panic!("got {:?}", s),
+        })
+        .collect();
+    assert_eq!(
+        ops,
+        ["x Add", "a[i] Sar", "p.ok And", "x IOr", "x++", "a[0]--"]
+    );
+
+    // as a for step
+    match parser::parse_stmt("for (var i = 0; i < n; i++;) { }")
+        .unwrap()
+        .elt
+        .node
+    {
+        Stmt::For(_, _, Some(step), _) => assert!(matches!(step.elt.node, Stmt::Incr(_))),
+        s => panic!("got {:?}", s),
+    }
+
+    // `--` is one token: a double negation needs a space
+    assert!(parser::parse_exp("x--y").is_err());
+    assert_eq!(shape(&exp("x - -y")), "(x Sub (Neg y))");
+}
+
+#[test]
+fn test_for_each() {
+    match parser::parse_stmt("for (x in a[0]) { s = s + x; }")
+        .unwrap()
+        .elt
+        .node
+    {
+        Stmt::ForEach(x, arr, body) => {
+            assert_eq!(x, "x");
+            assert_eq!(shape(&arr), "a[0]");
+            assert_eq!(body.len(), 1);
+        }
+        s => panic!("got {:?}", s),
+    }
+    // an ordinary for loop may still start with an identifier
+    assert!(matches!(
+        parser::parse_stmt("for (; i < n; i = i + 1) { }")
+            .unwrap()
+            .elt
+            .node,
+        Stmt::For(_, _, _, _)
+    ));
+    assert!(parser::parse_stmt("for (x in) { }").is_err());
+    assert!(parser::parse_stmt("for (int x in a) { }").is_err());
+
+    // `in` is an ordinary identifier everywhere else
+    assert!(matches!(
+        parser::parse_stmt("for (in in in) { }").unwrap().elt.node,
+        Stmt::ForEach(x, _, _) if x == "in"
+    ));
+    assert!(matches!(
+        parser::parse_stmt("for (var in = 0; in < n; in++) { }")
+            .unwrap()
+            .elt
+            .node,
+        Stmt::For(_, _, _, _)
+    ));
+    let (_, errs) = parser::parse_prog("int in(int[] in) { return in[0]; }");
+    assert!(errs.is_empty(), "{:?}", errs);
+}
+
+#[test]
+fn test_if_q() {
+    let b = body("if? (P p = q) { return; } else if? (int[] a = f()) { } else { x = 1; }");
+    match &b[0].elt.node {
+        Stmt::Cast(r, x, e, then, els) => {
+            assert_eq!(r.node, RefTy::RStruct("P".into()));
+            assert_eq!(x, "p");
+            assert_eq!(e.elt.node, Exp::Id("q".into()));
+            assert_eq!(then.len(), 1);
+            match &els[0].elt.node {
+                Stmt::Cast(r, _, _, _, els) => {
+                    assert!(matches!(r.node, RefTy::RArray(_)));
+                    assert!(matches!(els[0].elt.node, Stmt::Assn(_, _)));
+                }
+                s => panic!("got {:?}", s),
+            }
+        }
+        s => panic!("got {:?}", s),
+    }
+
+    // the declared type must be a non-null reference
+    for src in [
+        "if? (int x = y) { }",
+        "if? (P? p = q) { }",
+        "if? (p = q) { }",
+    ] {
+        assert!(parser::parse_stmt(src).is_err(), "accepted: {}", src);
+    }
+}
+
+#[test]
+fn test_declarations() {
+    let prog = parse_prog(
+        "global g = 3;
+         struct Point { int x; int y }
+         struct Empty { }
+         int[]? f(string s, (int, bool) -> void k, Point p) { return int[] null; }",
+    )
+    .unwrap();
+    assert_eq!(prog.len(), 4);
+
+    match &prog[1] {
+        Decl::GTDecl(t) => {
+            assert_eq!(t.elt.td_id, "Point");
+            assert_eq!(t.elt.td_node.len(), 2);
+        }
+        d => panic!("got {:?}", d),
+    }
+    match &prog[3] {
+        Decl::GFDecl(f) => {
+            assert!(
+                matches!(&f.elt.fret_ty, RetTy::RetVal(t) if matches!(t.node, Ty::TNullRef(_)))
+            );
+            assert_eq!(f.elt.args.len(), 3);
+            match &f.elt.args[1].ty.node {
+                Ty::TRef(r) => assert!(matches!(&r.node, RefTy::RFun(a, _) if a.len() == 2)),
+                t => panic!("got {:?}", t),
+            }
+        }
+        d => panic!("got {:?}", d),
+    }
+}
+
+#[test]
+fn test_spans() {
+    let src = "int f(int x) {\n  return x + 12 * 3;\n}";
+    let prog = parse_prog(src).unwrap();
+    let f = match &prog[0] {
+        Decl::GFDecl(f) => f,
+        d => panic!("got {:?}", d),
+    };
+    assert_eq!(f.loc, Span::new(0, src.len()));
+    assert_eq!(
+        &src[f.elt.args[0].ty.span.start..f.elt.args[0].ty.span.end],
+        "int"
+    );
+
+    let ret = &f.elt.body[0];
+    assert_eq!(&src[ret.loc.start..ret.loc.end], "return x + 12 * 3;");
+    let e = match &ret.elt.node {
+        Stmt::Ret(Some(e)) => e,
+        s => panic!("got {:?}", s),
+    };
+    assert_eq!(&src[e.loc.start..e.loc.end], "x + 12 * 3");
+    assert_eq!(e.loc, e.elt.span);
+    match &e.elt.node {
+        Exp::Bop(_, _, r) => assert_eq!(&src[r.loc.start..r.loc.end], "12 * 3"),
+        e => panic!("got {:?}", e),
+    }
+}
+
+#[test]
+fn test_syntax_errors() {
+    let errs = parse_prog("int f() { return 1 }").unwrap_err();
+    assert_eq!(errs.len(), 1);
+    let err = errs[0].clone();
+    match err.kind {
+        ParseErrorKind::Unexpected { expected, found } => {
+            assert_eq!(expected, "`;`");
+            assert_eq!(found, "}");
+        }
+        k => panic!("got {:?}", k),
+    }
+    assert_eq!(err.span, Span::new(19, 20));
+
+    // a bare expression is not a statement
+    assert!(parse_prog("void f() { 1 + 2; }").is_err());
+    // lexical errors come through as parse errors
+    let errs = parse_prog("void f() { var x = 1 $ 2; }").unwrap_err();
+    assert_eq!(errs.len(), 1);
+    assert!(matches!(errs[0].kind, ParseErrorKind::Lexical(_)));
+}
+
+#[test]
+fn test_recover_statements() {
+    let src = "void f() {
+        var x = 1 +;
+        var y = 2;
+        x = ;
+        if (x > ) { x = 1; }
+        return;
+    }";
+    let (prog, errs) = parser::parse_prog(src);
+    assert_eq!(error_texts(src, &errs), vec![";", ";", ")"]);
+
+    // the good statements survive
+    match &prog[0] {
+        Decl::GFDecl(f) => {
+            assert_eq!(f.elt.body.len(), 2);
+            assert!(matches!(f.elt.body[0].elt.node, Stmt::Decl(_)));
+            assert!(matches!(f.elt.body[1].elt.node, Stmt::Ret(None)));
+        }
+        d => panic!("got {:?}", d),
+    }
+}
+
+#[test]
+fn test_recover_missing_semicolon() {
+    let src = "void f() { var x = 1 var y = 2; return; }";
+    let (prog, errs) = parser::parse_prog(src);
+    assert_eq!(error_texts(src, &errs), vec!["var"]);
+    match &prog[0] {
+        Decl::GFDecl(f) => assert_eq!(f.elt.body.len(), 2),
+        d => panic!("got {:?}", d),
+    }
+}
+
+#[test]
+fn test_recover_declarations() {
+    let src = "struct S { int x; bool }
+        int f(int x { return x; }
+        global g = ;
+        void main() { }
+        int[] h() { return new int[]{1, 2}; }";
+    let (prog, errs) = parser::parse_prog(src);
+    assert_eq!(error_texts(src, &errs), vec!["}", "{", ";"]);
+
+    let names: Vec<_> = prog
+        .iter()
+        .filter_map(|d| match d {
+            Decl::GFDecl(f) => Some(f.elt.fname.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(names, vec!["main", "h"]);
+}
+
+#[test]
+fn test_recover_unclosed_block() {
+    let src = "void f() { var x = 1;\nstruct S { int x }\nvoid g() { }";
+    let (prog, errs) = parser::parse_prog(src);
+    assert_eq!(errs.len(), 1);
+    assert_eq!(error_texts(src, &errs), vec!["struct"]);
+    assert_eq!(prog.len(), 3);
+}
+
+#[test]
+fn test_lexical_and_syntax_errors_in_order() {
+    let src = "void f() { var x = 1 +; var s = \"abc;\n}\nint g() { return 1 $ 2; }";
+    let (_, errs) = parser::parse_prog(src);
+    let kinds: Vec<_> = errs
+        .iter()
+        .map(|e| matches!(e.kind, ParseErrorKind::Lexical(_)))
+        .collect();
+    // `+;`, the unterminated string, then the stray `$`; no cascades at the error tokens
+    assert_eq!(kinds, vec![false, true, true]);
+    assert!(errs.windows(2).all(|w| w[0].span.start <= w[1].span.start));
+}
+
+#[test]
+fn test_fragments() {
+    let e = exp("a[i] + 1");
+    assert_eq!(e.loc, Span::new(0, 8));
+    assert_eq!(shape(&e), "(a[i] Add 1)");
+
+    let s = parser::parse_stmt("while (i < n) { i = i + 1; }").unwrap();
+    assert!(matches!(s.elt.node, Stmt::While(_, _)));
+    assert!(matches!(
+        parser::parse_stmt("x = 1;").unwrap().elt.node,
+        Stmt::Assn(_, _)
+    ));
+
+    let t = parser::parse_ty("(int, string) -> bool[]?").unwrap();
+    match t.node {
+        Ty::TRef(r) => match r.node {
+            RefTy::RFun(args, ret) => {
+                assert_eq!(args.len(), 2);
+                assert!(matches!(&ret.node, RetTy::RetVal(t) if matches!(t.node, Ty::TNullRef(_))));
+            }
+            r => panic!("got {:?}", r),
+        },
+        t => panic!("got {:?}", t),
+    }
+    assert_eq!(parser::parse_ty("int").unwrap().node, Ty::TInt);
+}
+
+#[test]
+fn test_parenthesized_types() {
+    let ty = |src: &str| parser::parse_ty(src).unwrap().node;
+    let is_fun = |t: &Ty| matches!(t, Ty::TRef(r) if matches!(r.node, RefTy::RFun(..)));
+
+    // an array of functions, a nullable function, and a function returning an array
+    match ty("((int) -> int)[]") {
+        Ty::TRef(r) => match r.node {
+            RefTy::RArray(t) => assert!(is_fun(&t.node), "got {:?}", t.node),
+            r => panic!("got {:?}", r),
+        },
+        t => panic!("got {:?}", t),
+    }
+    assert!(matches!(ty("((int) -> int)?"), Ty::TNullRef(r) if matches!(r.node, RefTy::RFun(..))));
+    assert!(is_fun(&ty("(int) -> int[]")));
+
+    // parentheses around any other type change nothing
+    assert_eq!(ty("(int)"), Ty::TInt);
+    assert!(ty("((string))[]").eq_ignoring_spans(&ty("string[]")));
+
+    // as a declared type, and under `new`
+    let b = body("((int) -> int)[] fs = new ((int) -> int)[2]; ((int) -> int)? g = fs[0];");
+    assert_eq!(b.len(), 2);
+    assert!(parser::parse_ty("(int, bool)").is_err());
+    assert!(parser::parse_ty("()").is_err());
+}
+
+#[test]
+fn test_fragment_errors() {
+    // trailing input is an error
+    let errs = parser::parse_exp("1 + 2 3").unwrap_err();
+    assert_eq!(errs.len(), 1);
+    assert_eq!(errs[0].span, Span::new(6, 7));
+
+    assert!(parser::parse_exp("").is_err());
+    assert!(parser::parse_stmt("x = 1").is_err());
+    assert!(parser::parse_ty("int?").is_err());
+
+    // errors inside nested blocks are still reported
+    let errs = parser::parse_stmt("if (b) { x = ; } else { y = ; }").unwrap_err();
+    assert_eq!(errs.len(), 2);
+}
+
+#[test]
+fn test_typed_declarations() {
+    let b = body("int x = 1; int[]? a; Point p = q; var y = 2; x = 3;");
+    let vds: Vec<_> = b
+        .iter()
+        .filter_map(|s| match &s.elt.node {
+            Stmt::Decl(vd) => Some(vd),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(vds.len(), 4);
+    assert_eq!(vds[0].vd_ty.as_ref().unwrap().node, Ty::TInt);
+    assert!(vds[1].vd_node.is_none());
+    assert!(matches!(
+        vds[1].vd_ty.as_ref().unwrap().node,
+        Ty::TNullRef(_)
+    ));
+    assert!(matches!(vds[2].vd_ty.as_ref().unwrap().node, Ty::TRef(_)));
+    assert!(vds[3].vd_ty.is_none());
+    assert!(matches!(b[4].elt.node, Stmt::Assn(_, _)));
+
+    match parser::parse_stmt("for (int i = 0, bool b; i < 3; ) { }")
+        .unwrap()
+        .elt
+        .node
+    {
+        Stmt::For(vds, _, _, _) => {
+            assert_eq!(vds.len(), 2);
+            assert!(vds[1].vd_node.is_none());
+        }
+        s => panic!("got {:?}", s),
+    }
+}
+
+// ids of every statement and expression, in visiting order
+#[derive(Default)]
+struct NodeIds(Vec<NodeId>);
+
+impl Visitor for NodeIds {
+    fn visit_stmt(&mut self, s: &Node<SStmt>) {
+        self.0.push(s.id);
+        visit::walk_stmt(self, s);
+    }
+
+    fn visit_exp(&mut self, e: &Node<SExp>) {
+        self.0.push(e.id);
+        visit::walk_exp(self, e);
+    }
+}
+
+#[test]
+fn test_node_ids_are_unique_and_stable() {
+    let src = "global g = 1 + 2;
+        int f(int x) { var y = x * g; if (y > 0) { return y; } return -y; }";
+    let ids = |prog: &Prog| {
+        let mut v = NodeIds::default();
+        v.visit_prog(prog);
+        v.0
+    };
+    let first = ids(&parse_prog(src).unwrap());
+    let mut sorted = first.clone();
+    sorted.sort();
+    sorted.dedup();
+    assert_eq!(sorted.len(), first.len(), "duplicate ids in {:?}", first);
+    assert!(!first.contains(&NodeId::DUMMY));
+    // the same source numbers its nodes the same way every time
+    assert_eq!(ids(&parse_prog(src).unwrap()), first);
+}
+
+#[test]
+fn test_node_map() {
+    let e = exp("a + b");
+    let Exp::Bop(_, l, r) = &e.elt.node else {
+        panic!("expected a binop");
+    };
+    let mut names: NodeMap<&str> = NodeMap::new();
+    names.insert(l, "left");
+    names.insert(r, "right");
+    assert_eq!(names.get(l), Some(&"left"));
+    assert_eq!(names.get(r), Some(&"right"));
+    assert!(!names.contains(&e));
+    assert_eq!(names.insert(l, "again"), Some("left"));
+    assert_eq!(names.len(), 2);
+}
+
+#[test]
+fn test_eq_ignoring_spans() {
+    let e1 = exp("a[1] + f(x)");
+    let e2 = exp("  a[1]  +  f( x )");
+    assert_ne!(e1, e2);
+    assert!(e1.eq_ignoring_spans(&e2));
+    assert!(!e1.eq_ignoring_spans(&exp("a[1] + f(y)")));
+
+    let p1 = parse_prog("global g = new int[] {1, 2};").unwrap();
+    let p2 = parse_prog("global  g=new int[]{1,2};").unwrap();
+    assert_ne!(p1, p2);
+    assert!(p1.eq_ignoring_spans(&p2));
+}
@@ -0,0 +1,386 @@
+// Round-trip tests: parse(print(ast)) == ast, up to spans
+
+use ast::*;
+use common::{Span, Spanned};
+
+fn reparse(prog: &Prog) -> Prog {
+    let src = print_prog(prog);
+    let (back, errs) = parser::parse_prog(&src);
+    assert!(
+        errs.is_empty(),
+        "printed program does not parse: {:?}\n{}",
+        errs,
+        src
+    );
+    back
+}
+
+fn printed(src: &str) -> String {
+    parser::parse_exp(src).unwrap().elt.node.to_string()
+}
+
+#[test]
+fn test_minimal_parens() {
+    assert_eq!(printed("(1 + 2) + 3"), "1 + 2 + 3");
+    assert_eq!(printed("1 + (2 + 3)"), "1 + (2 + 3)");
+    assert_eq!(printed("(1 * 2) + (3 * 4)"), "1 * 2 + 3 * 4");
+    assert_eq!(printed("(1 + 2) * 3"), "(1 + 2) * 3");
+    assert_eq!(printed("-(a[1])"), "-a[1]");
+    assert_eq!(printed("(-a)[1]"), "(-a)[1]");
+    assert_eq!(printed("(-p).x + (p.y)"), "(-p).x + p.y");
+    assert_eq!(printed("new P{x=1;y=2;}"), "new P { x = 1; y = 2 }");
+    assert_eq!(printed("-(-(1))"), "- -1");
+    assert_eq!(printed("!(a & b)"), "!(a & b)");
+    assert_eq!(printed("(a | b) [&] c"), "a | b [&] c");
+    assert_eq!(printed("(a [|] b) & c"), "(a [|] b) & c");
+    assert_eq!(printed("\"a\\n\\\"b\\\"\""), "\"a\\n\\\"b\\\"\"");
+    assert_eq!(printed("new ((int) -> int)[3]"), "new ((int) -> int)[3]");
+    assert_eq!(
+        printed("new ((int) -> void)?[]{((int) -> void)? null}"),
+        "new ((int) -> void)?[]{(int) -> void null}"
+    );
+    assert_eq!(
+        printed("new int[][]{int[] null}"),
+        "new int[][]{int[] null}"
+    );
+}
+
+#[test]
+fn test_print_program() {
+    let src = "struct P {
+    int x;
+    bool[]? ys;
+}
+
+int f((int) -> void k, P p) {
+    for (var i = 0; i < 3; i = i + 1) {
+        k(i);
+    }
+    if (px > 0) { }
+    return 0;
+}
+";
+    let (prog, errs) = parser::parse_prog(src);
+    assert!(errs.is_empty());
+    assert_eq!(print_prog(&prog), src);
+}
+
+// --- random programs ---
+
+// xorshift64: small, deterministic, no dependencies
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn chance(&mut self, n: u64) -> bool {
+        self.below(n) == 0
+    }
+}
+
+fn sp<T>(t: T) -> Spanned<T> {
+    Spanned::new(Span::new(0, 0), t)
+}
+
+fn nd<T>(t: T) -> Node<Spanned<T>> {
+    Node {
+        elt: sp(t),
+        loc: Span::new(0, 0),
+        id: NodeId::DUMMY,
+    }
+}
+
+fn gen_id(r: &mut Rng, prefix: &str) -> IdTy {
+    format!("{}{}", prefix, r.below(4)).into()
+}
+
+fn gen_ty(r: &mut Rng, depth: u32) -> Ty {
+    match r.below(if depth == 0 { 3 } else { 6 }) {
+        0 => Ty::TInt,
+        1 => Ty::TBool,
+        2 => Ty::TRef(sp(RefTy::RString)),
+        3 => Ty::TRef(sp(gen_ref(r, depth - 1))),
+        4 => Ty::TNullRef(sp(gen_ref(r, depth - 1))),
+        _ => Ty::TRef(sp(RefTy::RArray(Box::new(sp(gen_ty(r, depth - 1)))))),
+    }
+}
+
+fn gen_ref(r: &mut Rng, depth: u32) -> RefTy {
+    match r.below(4) {
+        0 => RefTy::RString,
+        1 => RefTy::RStruct(gen_id(r, "S")),
+        2 => RefTy::RArray(Box::new(sp(gen_ty(r, depth)))),
+        _ => {
+            let args = (0..r.below(3)).map(|_| sp(gen_ty(r, depth))).collect();
+            RefTy::RFun(args, Box::new(sp(gen_ret(r, depth))))
+        }
+    }
+}
+
+fn gen_ret(r: &mut Rng, depth: u32) -> RetTy {
+    if r.chance(3) {
+        RetTy::RetVoid
+    } else {
+        RetTy::RetVal(Box::new(sp(gen_ty(r, depth))))
+    }
+}
+
+const BINOPS: [BinOp; 18] = [
+    BinOp::Add,
+    BinOp::Sub,
+    BinOp::Mul,
+    BinOp::Div,
+    BinOp::Mod,
+    BinOp::Eq,
+    BinOp::Neq,
+    BinOp::Lt,
+    BinOp::Lte,
+    BinOp::Gt,
+    BinOp::Gte,
+    BinOp::And,
+    BinOp::Or,
+    BinOp::IAnd,
+    BinOp::IOr,
+    BinOp::Shl,
+    BinOp::Shr,
+    BinOp::Sar,
+];
+
+// the operators with a compound assignment form `op=`
+const OP_ASSIGN: [BinOp; 12] = [
+    BinOp::Add,
+    BinOp::Sub,
+    BinOp::Mul,
+    BinOp::Div,
+    BinOp::Mod,
+    BinOp::Shl,
+    BinOp::Shr,
+    BinOp::Sar,
+    BinOp::And,
+    BinOp::Or,
+    BinOp::IAnd,
+    BinOp::IOr,
+];
+
+const UNOPS: [UnOp; 3] = [UnOp::Neg, UnOp::LogNot, UnOp::BitNot];
+
+fn gen_exps(r: &mut Rng, depth: u32) -> Vec<Node<SExp>> {
+    (0..r.below(3)).map(|_| gen_exp(r, depth)).collect()
+}
+
+fn gen_str(r: &mut Rng) -> String {
+    const CHARS: &[char] = &['a', 'z', ' ', '\n', '\t', '"', '\\', '\''];
+    (0..r.below(5))
+        .map(|_| CHARS[r.below(CHARS.len() as u64) as usize])
+        .collect()
+}
+
+fn gen_exp(r: &mut Rng, depth: u32) -> Node<SExp> {
+    let e = match r.below(if depth == 0 { 5 } else { 16 }) {
+        // non-negative only: `-1` reads back as `Neg(1)`
+        0 => Exp::CInt((r.next() >> 1) as i64),
+        1 => Exp::CBool(r.chance(2)),
+        2 => Exp::CStr(gen_str(r)),
+        3 => Exp::Id(gen_id(r, "x")),
+        4 => Exp::CNull(sp(gen_ref(r, 1))),
+        5 => Exp::CArr(gen_ty(r, 1), gen_exps(r, depth - 1)),
+        6 => Exp::NewArr(gen_ty(r, 1), Box::new(gen_exp(r, depth - 1))),
+        7 => Exp::Index(
+            Box::new(gen_exp(r, depth - 1)),
+            Box::new(gen_exp(r, depth - 1)),
+        ),
+        8 => Exp::Call(Box::new(gen_exp(r, depth - 1)), gen_exps(r, depth - 1)),
+        9 => Exp::CStruct(
+            gen_id(r, "S"),
+            (0..r.below(3))
+                .map(|_| CField {
+                    cf_id: gen_id(r, "f"),
+                    cf_node: gen_exp(r, depth - 1),
+                })
+                .collect(),
+        ),
+        10 => Exp::Proj(Box::new(gen_exp(r, depth - 1)), gen_id(r, "f")),
+        11 => Exp::Length(Box::new(gen_exp(r, depth - 1))),
+        12 => Exp::Uop(
+            UNOPS[r.below(3) as usize].clone(),
+            Box::new(gen_exp(r, depth - 1)),
+        ),
+        13 => Exp::Cond(
+            Box::new(gen_exp(r, depth - 1)),
+            Box::new(gen_exp(r, depth - 1)),
+            Box::new(gen_exp(r, depth - 1)),
+        ),
+        _ => Exp::Bop(
+            BINOPS[r.below(18) as usize].clone(),
+            Box::new(gen_exp(r, depth - 1)),
+            Box::new(gen_exp(r, depth - 1)),
+        ),
+    };
+    nd(e)
+}
+
+fn gen_vdecl(r: &mut Rng, depth: u32) -> VDecl {
+    let vd_ty = if r.chance(2) {
+        Some(sp(gen_ty(r, 2)))
+    } else {
+        None
+    };
+    // only typed declarations may leave out the initializer
+    let vd_node = if vd_ty.is_some() && r.chance(3) {
+        None
+    } else {
+        Some(gen_exp(r, depth))
+    };
+    VDecl {
+        vd_id: gen_id(r, "x"),
+        vd_ty,
+        vd_node,
+    }
+}
+
+fn gen_simple_stmt(r: &mut Rng, depth: u32) -> Node<SStmt> {
+    let s = match r.below(6) {
+        0 => Stmt::Decl(gen_vdecl(r, depth)),
+        1 => Stmt::Assn(gen_exp(r, depth), gen_exp(r, depth)),
+        2 => Stmt::OpAssn(
+            gen_exp(r, depth),
+            OP_ASSIGN[r.below(12) as usize].clone(),
+            gen_exp(r, depth),
+        ),
+        3 => Stmt::Incr(gen_exp(r, depth)),
+        4 => Stmt::Decr(gen_exp(r, depth)),
+        _ => Stmt::SCall(gen_exp(r, depth), gen_exps(r, depth)),
+    };
+    nd(s)
+}
+
+fn gen_block(r: &mut Rng, depth: u32) -> Block {
+    (0..r.below(4)).map(|_| gen_stmt(r, depth)).collect()
+}
+
+fn gen_stmt(r: &mut Rng, depth: u32) -> Node<SStmt> {
+    if depth == 0 {
+        return gen_simple_stmt(r, 0);
+    }
+    let s = match r.below(8) {
+        0 => return gen_simple_stmt(r, depth),
+        5 => {
+            if r.chance(2) {
+                Stmt::Break
+            } else {
+                Stmt::Continue
+            }
+        }
+        1 => Stmt::Ret(if r.chance(2) {
+            None
+        } else {
+            Some(gen_exp(r, depth))
+        }),
+        2 => Stmt::If(
+            gen_exp(r, depth),
+            gen_block(r, depth - 1),
+            gen_block(r, depth - 1),
+        ),
+        3 => Stmt::While(gen_exp(r, depth), gen_block(r, depth - 1)),
+        7 => Stmt::ForEach(gen_id(r, "x"), gen_exp(r, depth), gen_block(r, depth - 1)),
+        6 => Stmt::Cast(
+            sp(gen_ref(r, 1)),
+            gen_id(r, "x"),
+            gen_exp(r, depth),
+            gen_block(r, depth - 1),
+            gen_block(r, depth - 1),
+        ),
+        _ => Stmt::For(
+            (0..r.below(3)).map(|_| gen_vdecl(r, depth)).collect(),
+            if r.chance(3) {
+                None
+            } else {
+                Some(gen_exp(r, depth))
+            },
+            if r.chance(3) {
+                None
+            } else {
+                Some(Box::new(gen_simple_stmt(r, depth)))
+            },
+            gen_block(r, depth - 1),
+        ),
+    };
+    nd(s)
+}
+
+fn gen_decl(r: &mut Rng) -> Decl {
+    let loc = Span::new(0, 0);
+    match r.below(3) {
+        0 => Decl::GVDecl(Node {
+            elt: GDecl {
+                name: gen_id(r, "g"),
+                init: gen_exp(r, 3),
+            },
+            loc,
+            id: NodeId::DUMMY,
+        }),
+        1 => Decl::GTDecl(Node {
+            elt: TDecl {
+                td_id: gen_id(r, "S"),
+                td_node: (0..r.below(4))
+                    .map(|_| Field {
+                        field_name: gen_id(r, "f"),
+                        field_type: gen_ty(r, 2),
+                    })
+                    .collect(),
+            },
+            loc,
+            id: NodeId::DUMMY,
+        }),
+        _ => Decl::GFDecl(Node {
+            elt: FDecl {
+                fret_ty: gen_ret(r, 2),
+                fname: gen_id(r, "fn"),
+                args: (0..r.below(3))
+                    .map(|_| Arg {
+                        ty: sp(gen_ty(r, 2)),
+                        id: gen_id(r, "a"),
+                    })
+                    .collect(),
+                body: gen_block(r, 3),
+            },
+            loc,
+            id: NodeId::DUMMY,
+        }),
+    }
+}
+
+#[test]
+fn test_roundtrip_random_programs() {
+    let mut r = Rng(0x9e37_79b9_7f4a_7c15);
+    for _ in 0..500 {
+        let prog: Prog = (0..1 + r.below(4)).map(|_| gen_decl(&mut r)).collect();
+        let back = reparse(&prog);
+        assert!(
+            back.eq_ignoring_spans(&prog),
+            "round trip changed:\n{}",
+            print_prog(&prog)
+        );
+    }
+}
+
+#[test]
+fn test_roundtrip_random_expressions() {
+    let mut r = Rng(42);
+    for _ in 0..2000 {
+        let e = gen_exp(&mut r, 5);
+        let src = e.elt.node.to_string();
+        let back = parser::parse_exp(&src)
+            .unwrap_or_else(|errs| panic!("`{}` does not parse: {:?}", src, errs));
+        assert!(back.eq_ignoring_spans(&e), "round trip changed `{}`", src);
+    }
+}
@@ -0,0 +1,106 @@
+// Visitor / VisitorMut over parsed programs
+
+use ast::visit::*;
+use ast::*;
+use common::Spanned;
+
+fn parse(src: &str) -> Prog {
+    let (prog, errs) = parser::parse_prog(src);
+    assert!(errs.is_empty(), "{:?}", errs);
+    prog
+}
+
+// every identifier, in visiting order
+#[derive(Default)]
+struct Ids(Vec<String>);
+
+impl Visitor for Ids {
+    fn visit_exp(&mut self, e: &Node<SExp>) {
+        if let Exp::Id(x) = &e.elt.node {
+            self.0.push(x.to_string());
+        }
+        walk_exp(self, e);
+    }
+}
+
+#[test]
+fn test_visits_every_expression_in_source_order() {
+    let prog = parse(
+        "struct P { int x; }
+        global g = new int[]{a1, a2};
+        int f(int[] a, P? p) {
+            var v = b1 ? b2 : b3;
+            a[i1] += length(c1);
+            i2++;
+            for (var j = d1; d2; j = d3;) { }
+            for (x in e1) { if (e2) { return e3; } else { break; } }
+            while (e4) { h(e5, new P { x = e6 }.x); }
+            if? (P q = e7) { } else { return -e8 / e9; }
+            return new int[e10][0];
+        }",
+    );
+    let mut ids = Ids::default();
+    ids.visit_prog(&prog);
+    let expected = [
+        "a1", "a2", "b1", "b2", "b3", "a", "i1", "c1", "i2", "d1", "d2", "j", "d3", "e1", "e2",
+        "e3", "e4", "h", "e5", "e6", "e7", "e8", "e9", "e10",
+    ];
+    assert_eq!(ids.0, expected);
+}
+
+// overriding a method without walking stops the descent there
+#[test]
+fn test_override_can_skip_children() {
+    struct TopLevelCalls(usize);
+    impl Visitor for TopLevelCalls {
+        fn visit_exp(&mut self, e: &Node<SExp>) {
+            if let Exp::Call(..) = e.elt.node {
+                self.0 += 1;
+            } else {
+                walk_exp(self, e);
+            }
+        }
+    }
+
+    let prog = parse("int f() { return g(g(1)) + g(2); }");
+    let mut v = TopLevelCalls(0);
+    v.visit_prog(&prog);
+    assert_eq!(v.0, 2);
+}
+
+// desugar `x++` / `x--` to `x += 1` / `x -= 1` and rename identifiers
+#[test]
+fn test_visitor_mut_rewrites_in_place() {
+    struct Desugar;
+    impl VisitorMut for Desugar {
+        fn visit_stmt(&mut self, s: &mut Node<SStmt>) {
+            let one = |s: &Node<SStmt>| Node {
+                elt: Spanned::new(s.loc.clone(), Exp::CInt(1)),
+                loc: s.loc.clone(),
+                id: NodeId::DUMMY,
+            };
+            let node = match &s.elt.node {
+                Stmt::Incr(lhs) => Some(Stmt::OpAssn(lhs.clone(), BinOp::Add, one(s))),
+                Stmt::Decr(lhs) => Some(Stmt::OpAssn(lhs.clone(), BinOp::Sub, one(s))),
+                _ => None,
+            };
+            if let Some(node) = node {
+                s.elt.node = node;
+            }
+            walk_stmt_mut(self, s);
+        }
+
+        fn visit_exp(&mut self, e: &mut Node<SExp>) {
+            if let Exp::Id(x) = &mut e.elt.node {
+                *x = format!("{}_", x).into();
+            }
+            walk_exp_mut(self, e);
+        }
+    }
+
+    let mut prog = parse("void f(int[] a) { for (var i = 0; i < 3; i++;) { a[i]--; } return; }");
+    Desugar.visit_prog(&mut prog);
+    assert!(prog.eq_ignoring_spans(&parse(
+        "void f(int[] a) { for (var i = 0; i_ < 3; i_ += 1;) { a_[i_] -= 1; } return; }"
+    )));
+}
@@ -15,12 +15,24 @@ type GlobalCtxt = HashMap<IdTy, Ty>;
 type FunCtxt = HashMap<IdTy, FunTy>;
 type StructCtxt = HashMap<IdTy, Vec<Field>>;
 
+// What typechecking found out about a program, for the phases after it:
+// the type of every expression, and the type of the function every call
+// (expression or statement) resolved to, both keyed by the node
+#[derive(Debug, Clone, Default)]
+pub struct TypeTable {
+    pub exps: NodeMap<STy>,
+    pub calls: NodeMap<(Vec<STy>, SRetTy)>,
+}
+
 #[derive(Debug, Clone)]
 pub struct TypeCtxt {
     locals: LocalCtxt,
     globals: GlobalCtxt,
     functions: FunCtxt,
     structs: StructCtxt,
+    loops: usize, // number of enclosing loop bodies
+    types: TypeTable,
+    errors: Vec<TypeError>,
 }
 
 impl TypeCtxt {
@@ -30,6 +42,9 @@ impl TypeCtxt {
             globals: HashMap::new(),
             functions: HashMap::new(),
             structs: HashMap::new(),
+            loops: 0,
+            types: TypeTable::default(),
+            errors: Vec::new(),
         }
     }
 
@@ -51,9 +66,9 @@ impl TypeCtxt {
         self.locals.last_mut().unwrap().insert(id, ty);
     }
 
-    pub fn lookup_local_option(&self, id: &str) -> Option<&Ty> {
+    pub fn lookup_local_option(&self, id: IdTy) -> Option<&Ty> {
         for scope in self.locals.iter().rev() {
-            if let Some(t) = scope.get(id) {
+            if let Some(t) = scope.get(&id) {
                 return Some(t);
             }
         }
@@ -61,10 +76,23 @@ impl TypeCtxt {
     }
 
     // Check if a variable is declared in the current scope only (not parent scopes)
-    pub fn is_declared_in_current_scope(&self, id: &str) -> bool {
+    pub fn is_declared_in_current_scope(&self, id: IdTy) -> bool {
         self.locals
             .last()
-            .map_or(false, |scope| scope.contains_key(id))
+            .is_some_and(|scope| scope.contains_key(&id))
+    }
+
+    // ----- loops (for break & continue) -----
+    pub fn enter_loop(&mut self) {
+        self.loops += 1;
+    }
+
+    pub fn exit_loop(&mut self) {
+        self.loops -= 1;
+    }
+
+    pub fn in_loop(&self) -> bool {
+        self.loops > 0
     }
 
     // ----- globals -----
@@ -72,12 +100,12 @@ impl TypeCtxt {
         self.globals.insert(id, ty);
     }
 
-    pub fn lookup_global_option(&self, id: &str) -> Option<&Ty> {
-        self.globals.get(id)
+    pub fn lookup_global_option(&self, id: IdTy) -> Option<&Ty> {
+        self.globals.get(&id)
     }
 
     // general lookup for : local? global
-    pub fn lookup_var_option(&self, id: &str) -> Option<&Ty> {
+    pub fn lookup_var_option(&self, id: IdTy) -> Option<&Ty> {
         self.lookup_local_option(id)
             .or_else(|| self.lookup_global_option(id))
     }
@@ -87,8 +115,8 @@ impl TypeCtxt {
         self.functions.insert(id, fty);
     }
 
-    pub fn lookup_function_option(&self, id: &str) -> Option<&FunTy> {
-        self.functions.get(id)
+    pub fn lookup_function_option(&self, id: IdTy) -> Option<&FunTy> {
+        self.functions.get(&id)
     }
 
     // ----- structs -----
@@ -96,28 +124,80 @@ impl TypeCtxt {
         self.structs.insert(id, fields);
     }
 
-    pub fn lookup_struct_option(&self, id: &str) -> Option<&[Field]> {
-        self.structs.get(id).map(|v| v.as_slice())
+    pub fn lookup_struct_option(&self, id: IdTy) -> Option<&[Field]> {
+        self.structs.get(&id).map(|v| v.as_slice())
     }
 
-    pub fn lookup_field_option(&self, st_name: &str, f_name: &str) -> Option<&Ty> {
+    pub fn lookup_field_option(&self, st_name: IdTy, f_name: IdTy) -> Option<&Ty> {
         let fields = self.lookup_struct_option(st_name)?;
         fields
             .iter()
             .find(|f| f.field_name == f_name)
             .map(|f| &f.field_type)
     }
+
+    // ----- results -----
+    pub fn record_exp(&mut self, e: &Node<SExp>, t: STy) {
+        self.types.exps.insert(e, t);
+    }
+
+    pub fn record_call<T>(&mut self, call: &Node<T>, args: &[STy], ret: &SRetTy) {
+        self.types.calls.insert(call, (args.to_vec(), ret.clone()));
+    }
+
+    // ----- errors -----
+
+    // record the error of a failed check, if it has one of its own
+    pub fn report(&mut self, e: TcError) {
+        if let TcError::Error(e) = e {
+            self.errors.push(e);
+        }
+    }
+
+    // keep going after a check: its result, or None once its error is reported
+    pub fn check<T>(&mut self, r: TcResult<T>) -> Option<T> {
+        r.map_err(|e| self.report(e)).ok()
+    }
+
+    // the type table, and every error reported, in source order
+    pub fn into_results(self) -> (TypeTable, Vec<TypeError>) {
+        let mut errors = self.errors;
+        errors.sort_by_key(|e| (e.span.start, e.span.end));
+        (self.types, errors)
+    }
 }
 
 // Typechecker utilitites -------------------------------------------------------------------
-pub type TcResult<T> = Result<T, TypeError>;
 
-pub fn type_error(msg: impl Into<String>, span: Span, kind: TypeErrorKind) -> TypeError {
-    TypeError {
+// Why a check failed: with a new error, or because something it needed has the
+// poison type, whose error was reported already (so it has nothing to add)
+#[derive(Debug, Clone)]
+pub enum TcError {
+    Error(TypeError),
+    Poisoned,
+}
+
+pub type TcResult<T> = Result<T, TcError>;
+
+pub fn type_error(msg: impl Into<String>, span: Span, kind: TypeErrorKind) -> TcError {
+    TcError::Error(TypeError {
         msg: msg.into(),
         span,
         kind,
-    }
+    })
+}
+
+// The poison type: what `var x = e;` gives x when e is ill-typed, so that uses
+// of x fail silently instead of each reporting "Unbound identifier x". It is
+// a struct type no Oat program can name, and it never leaves the typechecker.
+const POISON: &str = "<error>";
+
+pub fn t_poison(span: Span) -> Ty {
+    Ty::TRef(mk_srefty(RefTy::RStruct(POISON.into()), span))
+}
+
+pub fn is_poison(t: &Ty) -> bool {
+    matches!(t, Ty::TRef(r) if matches!(&r.node, RefTy::RStruct(s) if *s == POISON))
 }
 
 // Typechecking-rule helpers (ex. constructors)
@@ -165,7 +245,9 @@ pub fn mk_r_array(elt: Box<ast::STy>, span: common::Span) -> ast::SRefTy {
 pub fn typ_of_binop(b: &ast::BinOp) -> (Ty, Ty, Ty) {
     use ast::BinOp::*;
     match b {
-        Add | Sub | Mul | IAnd | IOr | Shl | Shr | Sar => (Ty::TInt, Ty::TInt, Ty::TInt),
+        Add | Sub | Mul | Div | Mod | IAnd | IOr | Shl | Shr | Sar => {
+            (Ty::TInt, Ty::TInt, Ty::TInt)
+        }
         Eq | Neq | Lt | Lte | Gt | Gte => (Ty::TInt, Ty::TInt, Ty::TBool),
         And | Or => (Ty::TBool, Ty::TBool, Ty::TBool),
     }
@@ -182,5 +264,5 @@ pub fn typ_of_unop(u: &ast::UnOp) -> (Ty, Ty) {
 // Typechecker implementation module
 pub mod typechecker;
 
-// Re-export main typechecking function for external use
-pub use typechecker::typecheck_prog;
+// Re-export the typechecking entry points for external use
+pub use typechecker::{typecheck_prog, typecheck_prog_all};
@@ -15,6 +15,6 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     eprintln!("Example usage:");
     eprintln!("  use typechecker::typecheck_prog;");
     eprintln!("  let program = /* parsed AST */;");
-    eprintln!("  typecheck_prog(&program)?;");
+    eprintln!("  let types = typecheck_prog(&program)?; // TypeTable");
     Ok(())
 }
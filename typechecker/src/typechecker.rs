@@ -2,7 +2,6 @@
 // Contains all expression, statement, and program-level typechecking functions
 
 use crate::*;
-use ast::*;
 use common::TypeErrorKind;
 
 // SUBTYPING RULES ------------------------------------------------------
@@ -11,10 +10,10 @@ fn subtype(h: &TypeCtxt, t1: &ast::STy, t2: &ast::STy) -> bool {
     match (&t1.node, &t2.node) {
         (Ty::TInt, Ty::TInt) => true,
         (Ty::TBool, Ty::TBool) => true,
+        // R? is not a subtype of R: nullable values must go through if? first
         (Ty::TNullRef(rty1), Ty::TNullRef(rty2))
         | (Ty::TRef(rty1), Ty::TNullRef(rty2))
-        | (Ty::TRef(rty1), Ty::TRef(rty2))
-        | (Ty::TNullRef(rty1), Ty::TRef(rty2)) => subtype_ref(h, rty1, rty2),
+        | (Ty::TRef(rty1), Ty::TRef(rty2)) => subtype_ref(h, rty1, rty2),
         (_, _) => false,
     }
 }
@@ -22,14 +21,12 @@ fn subtype(h: &TypeCtxt, t1: &ast::STy, t2: &ast::STy) -> bool {
 fn subtype_ref(h: &TypeCtxt, t1: &ast::SRefTy, t2: &ast::SRefTy) -> bool {
     match (&t1.node, &t2.node) {
         (RefTy::RString, RefTy::RString) => true,
-        (RefTy::RArray(elt_t1), RefTy::RArray(elt_t2)) => elt_t1.node == elt_t2.node,
+        (RefTy::RArray(elt_t1), RefTy::RArray(elt_t2)) => elt_t1.eq_ignoring_spans(elt_t2),
         (RefTy::RFun(args1, out1), RefTy::RFun(args2, out2)) => {
             subtype_list(h, args2.as_slice(), args1.as_slice())
                 && subtype_ret(h, out1.as_ref(), out2.as_ref())
         }
-        (RefTy::RStruct(id1), RefTy::RStruct(id2)) => {
-            id1 == id2 || subtype_fields(h, id1.to_string(), id2.to_string())
-        }
+        (RefTy::RStruct(id1), RefTy::RStruct(id2)) => id1 == id2 || subtype_fields(h, *id1, *id2),
         (_, _) => false,
     }
 }
@@ -49,8 +46,71 @@ fn subtype_list(h: &TypeCtxt, l1: &[ast::STy], l2: &[ast::STy]) -> bool {
     l1.iter().zip(l2.iter()).all(|(t1, t2)| subtype(h, t1, t2))
 }
 
-fn subtype_fields(_h: &TypeCtxt, _n1: ast::IdTy, _n2: ast::IdTy) -> bool {
-    false
+// width subtyping: S1 <: S2 when S2's fields are a prefix of S1's, with the
+// same names and types in the same order
+fn subtype_fields(h: &TypeCtxt, n1: ast::IdTy, n2: ast::IdTy) -> bool {
+    match (h.lookup_struct_option(n1), h.lookup_struct_option(n2)) {
+        (Some(fs1), Some(fs2)) => {
+            fs2.len() <= fs1.len() && fs1.iter().zip(fs2).all(|(f1, f2)| f1.eq_ignoring_spans(f2))
+        }
+        _ => false,
+    }
+}
+
+// least common supertype of t1 and t2, if one exists that is either of them,
+// or their nullable version when only one is nullable (R? and S with S <: R
+// join to R?)
+fn join(h: &TypeCtxt, t1: &ast::STy, t2: &ast::STy) -> Option<Ty> {
+    if subtype(h, t1, t2) {
+        return Some(t2.node.clone());
+    }
+    if subtype(h, t2, t1) {
+        return Some(t1.node.clone());
+    }
+    match (&t1.node, &t2.node) {
+        (Ty::TRef(r1) | Ty::TNullRef(r1), Ty::TRef(r2) | Ty::TNullRef(r2)) => {
+            if subtype_ref(h, r1, r2) {
+                Some(Ty::TNullRef(r2.clone()))
+            } else if subtype_ref(h, r2, r1) {
+                Some(Ty::TNullRef(r1.clone()))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+// NULLABILITY DIAGNOSTICS ----------------------------------------------
+
+// `what` (index, call, ...) applied to a value of nullable type t = R?
+fn null_use_error(what: &str, t: &ast::STy, loc: common::Span) -> TcError {
+    let r = match &t.node {
+        Ty::TNullRef(r) => r,
+        _ => unreachable!("null_use_error on a non-nullable type"),
+    };
+    type_error(
+        format!(
+            "Cannot {} a value of type {} that may be null; check it first with `if? ({} x = ...) {{ ... }}`",
+            what, t.node, r.node
+        ),
+        loc,
+        TypeErrorKind::Mismatch {
+            expected: format!("{:?}", Ty::TRef(r.clone())),
+            found: format!("{:?}", t.node),
+        },
+    )
+}
+
+// `msg`, plus a pointer to if? when `found` is only a nullable version of `expected`
+fn mismatch_msg(h: &TypeCtxt, msg: &str, found: &ast::STy, expected: &ast::STy) -> String {
+    match (&found.node, &expected.node) {
+        (Ty::TNullRef(r1), Ty::TRef(r2)) if subtype_ref(h, r1, r2) => format!(
+            "{}: {} may be null; check it first with `if? ({} x = ...) {{ ... }}`",
+            msg, found.node, r2.node
+        ),
+        _ => msg.to_string(),
+    }
 }
 
 // WELL-FORMEDNESS CHECKING ---------------------------------------------
@@ -66,11 +126,13 @@ fn typecheck_ref(h: &TypeCtxt, r: &ast::SRefTy) -> TcResult<()> {
     match &r.node {
         RefTy::RString => Ok(()),
         RefTy::RStruct(id) => {
-            if h.lookup_struct_option(id.as_str()).is_none() {
+            if h.lookup_struct_option(*id).is_none() {
                 Err(type_error(
                     format!("Unbound struct type for {}", id),
                     r.span.clone(),
-                    TypeErrorKind::UnknownIdentifier { name: id.clone() },
+                    TypeErrorKind::UnknownStruct {
+                        name: id.to_string(),
+                    },
                 ))
             } else {
                 Ok(())
@@ -96,7 +158,30 @@ fn typecheck_ret(h: &TypeCtxt, ret: &ast::SRetTy) -> TcResult<()> {
 
 // EXPRESSION TYPECHECKING ----------------------------------------------
 
-fn typecheck_exp(h: &TypeCtxt, e: &ast::Node<ast::SExp>) -> TcResult<ast::STy> {
+// the type of `e`, recorded in the context's type table
+//
+// an error is reported right here, where it is found; the caller only sees
+// `Poisoned`, so the expressions around this one don't report it again
+fn typecheck_exp(h: &mut TypeCtxt, e: &ast::Node<ast::SExp>) -> TcResult<ast::STy> {
+    match typecheck_exp_node(h, e) {
+        Ok(t) => {
+            h.record_exp(e, t.clone());
+            Ok(t)
+        }
+        Err(err) => {
+            h.report(err);
+            Err(TcError::Poisoned)
+        }
+    }
+}
+
+// the types of `es`, checking every one even after one fails
+fn typecheck_exps(h: &mut TypeCtxt, es: &[ast::Node<ast::SExp>]) -> TcResult<Vec<ast::STy>> {
+    let tys: Vec<_> = es.iter().map(|e| typecheck_exp(h, e)).collect();
+    tys.into_iter().collect()
+}
+
+fn typecheck_exp_node(h: &mut TypeCtxt, e: &ast::Node<ast::SExp>) -> TcResult<ast::STy> {
     let span = e.loc.clone();
 
     match &e.elt.node {
@@ -111,12 +196,15 @@ fn typecheck_exp(h: &TypeCtxt, e: &ast::Node<ast::SExp>) -> TcResult<ast::STy> {
             Ok(mk_sty(Ty::TRef(rstring), span))
         }
 
-        Exp::Id(id) => match h.lookup_var_option(id.as_str()) {
+        Exp::Id(id) => match h.lookup_var_option(*id) {
+            Some(ty) if is_poison(ty) => Err(TcError::Poisoned),
             Some(ty) => Ok(mk_sty(ty.clone(), span)),
             None => Err(type_error(
                 format!("Unbound identifier {}", id),
                 span,
-                TypeErrorKind::UnknownIdentifier { name: id.clone() },
+                TypeErrorKind::UnknownIdentifier {
+                    name: id.to_string(),
+                },
             )),
         },
 
@@ -126,17 +214,14 @@ fn typecheck_exp(h: &TypeCtxt, e: &ast::Node<ast::SExp>) -> TcResult<ast::STy> {
             typecheck_ty(h, &sty)?;
 
             // Typecheck all elements
-            let mut types_of = Vec::new();
-            for elem in l {
-                types_of.push(typecheck_exp(h, elem)?);
-            }
+            let types_of = typecheck_exps(h, l)?;
 
             // Check that all elements are subtypes of t
             let sty_check = mk_sty(t.clone(), span.clone());
             for elem_ty in &types_of {
                 if !subtype(h, elem_ty, &sty_check) {
                     return Err(type_error(
-                        "Mismatched array type",
+                        mismatch_msg(h, "Mismatched array type", elem_ty, &sty_check),
                         span,
                         TypeErrorKind::Mismatch {
                             expected: format!("{:?}", t),
@@ -159,9 +244,8 @@ fn typecheck_exp(h: &TypeCtxt, e: &ast::Node<ast::SExp>) -> TcResult<ast::STy> {
                     return Err(type_error(
                         "Non-null types cannot be used with default-initialized arrays",
                         span,
-                        TypeErrorKind::Mismatch {
-                            expected: "nullable or primitive type".to_string(),
-                            found: format!("{:?}", t),
+                        TypeErrorKind::NoDefaultValue {
+                            ty: format!("{:?}", t),
                         },
                     ));
                 }
@@ -184,9 +268,134 @@ fn typecheck_exp(h: &TypeCtxt, e: &ast::Node<ast::SExp>) -> TcResult<ast::STy> {
             Ok(mk_sty(Ty::TRef(rarray), span))
         }
 
+        // new S { f1 = e1; ...; fn = en }: every field of S exactly once
+        Exp::CStruct(id, cfields) => {
+            let fields = match h.lookup_struct_option(*id) {
+                Some(fields) => fields.to_vec(),
+                None => {
+                    return Err(type_error(
+                        format!("Unbound struct type for {}", id),
+                        span,
+                        TypeErrorKind::UnknownStruct {
+                            name: id.to_string(),
+                        },
+                    ));
+                }
+            };
+
+            let mut seen = std::collections::HashSet::new();
+            for cf in cfields {
+                if !seen.insert(cf.cf_id.as_str()) {
+                    return Err(type_error(
+                        format!("Field '{}' initialized twice in struct '{}'", cf.cf_id, id),
+                        cf.cf_node.loc.clone(),
+                        TypeErrorKind::DuplicateField {
+                            struct_name: id.to_string(),
+                            field: cf.cf_id.to_string(),
+                        },
+                    ));
+                }
+                let field_ty = match fields.iter().find(|f| f.field_name == cf.cf_id) {
+                    Some(f) => mk_sty(f.field_type.clone(), span.clone()),
+                    None => {
+                        return Err(type_error(
+                            format!("Struct '{}' has no field '{}'", id, cf.cf_id),
+                            cf.cf_node.loc.clone(),
+                            TypeErrorKind::UnknownField {
+                                struct_name: id.to_string(),
+                                field: cf.cf_id.to_string(),
+                            },
+                        ));
+                    }
+                };
+                let init_ty = typecheck_exp(h, &cf.cf_node)?;
+                if !subtype(h, &init_ty, &field_ty) {
+                    return Err(type_error(
+                        mismatch_msg(
+                            h,
+                            &format!("Mismatched type for field '{}'", cf.cf_id),
+                            &init_ty,
+                            &field_ty,
+                        ),
+                        cf.cf_node.loc.clone(),
+                        TypeErrorKind::Mismatch {
+                            expected: format!("{:?}", field_ty.node),
+                            found: format!("{:?}", init_ty.node),
+                        },
+                    ));
+                }
+            }
+
+            if let Some(missing) = fields
+                .iter()
+                .find(|f| !seen.contains(f.field_name.as_str()))
+            {
+                return Err(type_error(
+                    format!("Missing field '{}' in struct '{}'", missing.field_name, id),
+                    span,
+                    TypeErrorKind::MissingField {
+                        struct_name: id.to_string(),
+                        field: missing.field_name.to_string(),
+                    },
+                ));
+            }
+
+            let rstruct = mk_srefty(RefTy::RStruct(*id), span.clone());
+            Ok(mk_t_ref(rstruct, span))
+        }
+
+        // e.f: e must be a struct with a field f
+        Exp::Proj(e1, field) => {
+            let st = typecheck_exp(h, e1)?;
+            let sname = match &st.node {
+                Ty::TRef(r) => match &r.node {
+                    RefTy::RStruct(sname) => *sname,
+                    _ => {
+                        return Err(type_error(
+                            format!("Cannot project field '{}' out of a non-struct", field),
+                            e1.loc.clone(),
+                            TypeErrorKind::Mismatch {
+                                expected: "struct type".to_string(),
+                                found: format!("{:?}", st.node),
+                            },
+                        ));
+                    }
+                },
+                Ty::TNullRef(_) => {
+                    return Err(null_use_error(
+                        &format!("project field '{}' out of", field),
+                        &st,
+                        e1.loc.clone(),
+                    ));
+                }
+                _ => {
+                    return Err(type_error(
+                        format!("Cannot project field '{}' out of a non-struct", field),
+                        e1.loc.clone(),
+                        TypeErrorKind::Mismatch {
+                            expected: "struct type".to_string(),
+                            found: format!("{:?}", st.node),
+                        },
+                    ));
+                }
+            };
+            match h.lookup_field_option(sname, *field) {
+                Some(t) => Ok(mk_sty(t.clone(), span)),
+                None => Err(type_error(
+                    format!("Struct '{}' has no field '{}'", sname, field),
+                    span,
+                    TypeErrorKind::UnknownField {
+                        struct_name: sname.to_string(),
+                        field: field.to_string(),
+                    },
+                )),
+            }
+        }
+
         Exp::Bop(b, l, r) => {
-            let ltyp = typecheck_exp(h, l)?;
-            let rtyp = typecheck_exp(h, r)?;
+            let ltyp = typecheck_exp(h, l);
+            let rtyp = typecheck_exp(h, r);
+            let (ltyp, rtyp) = (ltyp?, rtyp?);
 
             match b {
                 BinOp::Eq | BinOp::Neq => {
@@ -248,9 +457,35 @@ fn typecheck_exp(h: &TypeCtxt, e: &ast::Node<ast::SExp>) -> TcResult<ast::STy> {
             }
         }
 
+        // guard ? e1 : e2
+        //      guard : bool
+        //      e1 : t1, e2 : t2, result : the join of t1 and t2
+        Exp::Cond(guard, e1, e2) => {
+            let guard_ok = typecheck_guard(h, guard);
+            let t1 = typecheck_exp(h, e1);
+            let t2 = typecheck_exp(h, e2);
+            guard_ok?;
+            let (t1, t2) = (t1?, t2?);
+            match join(h, &t1, &t2) {
+                Some(t) => Ok(mk_sty(t, span)),
+                None => Err(type_error(
+                    format!(
+                        "Branches of conditional expression have incompatible types {} and {}",
+                        t1.node, t2.node
+                    ),
+                    e2.loc.clone(),
+                    TypeErrorKind::Mismatch {
+                        expected: format!("{:?}", t1.node),
+                        found: format!("{:?}", t2.node),
+                    },
+                )),
+            }
+        }
+
         Exp::Index(e1, e2) => {
-            let arr_t = typecheck_exp(h, e1)?;
-            let ind_t = typecheck_exp(h, e2)?;
+            let arr_t = typecheck_exp(h, e1);
+            let ind_t = typecheck_exp(h, e2);
+            let (arr_t, ind_t) = (arr_t?, ind_t?);
 
             if ind_t.node != Ty::TInt {
                 return Err(type_error(
@@ -269,45 +504,57 @@ fn typecheck_exp(h: &TypeCtxt, e: &ast::Node<ast::SExp>) -> TcResult<ast::STy> {
                     _ => Err(type_error(
                         format!("Tried to compute index into type {:?}", arr_t.node),
                         e1.loc.clone(),
-                        TypeErrorKind::Mismatch {
-                            expected: "array type".to_string(),
-                            found: format!("{:?}", arr_t.node),
+                        TypeErrorKind::NotIndexable {
+                            ty: format!("{:?}", arr_t.node),
                         },
                     )),
                 },
+                Ty::TNullRef(_) => Err(null_use_error("index into", &arr_t, e1.loc.clone())),
                 _ => Err(type_error(
                     format!("Tried to compute index into type {:?}", arr_t.node),
                     e1.loc.clone(),
-                    TypeErrorKind::Mismatch {
-                        expected: "array type".to_string(),
-                        found: format!("{:?}", arr_t.node),
+                    TypeErrorKind::NotIndexable {
+                        ty: format!("{:?}", arr_t.node),
                     },
                 )),
             }
         }
 
-        Exp::Call(f, args) => {
-            // typecheck args
-            let mut argtyps = Vec::new();
-            for arg in args {
-                argtyps.push(typecheck_exp(h, arg)?);
+        Exp::Length(e) => {
+            let arr_t = typecheck_exp(h, e)?;
+            match &arr_t.node {
+                Ty::TRef(r) if matches!(r.node, RefTy::RArray(_)) => Ok(mk_sty(Ty::TInt, span)),
+                Ty::TNullRef(_) => Err(null_use_error("take the length of", &arr_t, e.loc.clone())),
+                _ => Err(type_error(
+                    format!("Tried to compute length of type {:?}", arr_t.node),
+                    e.loc.clone(),
+                    TypeErrorKind::NotIndexable {
+                        ty: format!("{:?}", arr_t.node),
+                    },
+                )),
             }
+        }
 
-            // typecheck f
-            let ftyp = typecheck_exp(h, f)?;
+        Exp::Call(f, args) => {
+            // typecheck args, then f
+            let argtyps = typecheck_exps(h, args);
+            let ftyp = typecheck_exp(h, f);
+            let (argtyps, ftyp) = (argtyps?, ftyp?);
 
             match &ftyp.node {
                 Ty::TRef(r) => {
                     match &r.node {
                         RefTy::RFun(param_types, ret_ty) => {
+                            h.record_call(e, param_types, ret_ty);
+
                             // Check correct number of arguments
                             if param_types.len() != argtyps.len() {
                                 return Err(type_error(
                                     "Incorrect number of arguments",
                                     span,
-                                    TypeErrorKind::Mismatch {
-                                        expected: format!("{} arguments", param_types.len()),
-                                        found: format!("{} arguments", argtyps.len()),
+                                    TypeErrorKind::ArityMismatch {
+                                        expected: param_types.len(),
+                                        found: argtyps.len(),
                                     },
                                 ));
                             }
@@ -318,7 +565,12 @@ fn typecheck_exp(h: &TypeCtxt, e: &ast::Node<ast::SExp>) -> TcResult<ast::STy> {
                             {
                                 if !subtype(h, arg, param) {
                                     return Err(type_error(
-                                        format!("Incorrect type of argument {}", i),
+                                        mismatch_msg(
+                                            h,
+                                            &format!("Incorrect type of argument {}", i),
+                                            arg,
+                                            param,
+                                        ),
                                         span.clone(),
                                         TypeErrorKind::Mismatch {
                                             expected: format!("{:?}", param.node),
@@ -334,10 +586,7 @@ fn typecheck_exp(h: &TypeCtxt, e: &ast::Node<ast::SExp>) -> TcResult<ast::STy> {
                                 RetTy::RetVoid => Err(type_error(
                                     "Cannot use void function in expression context",
                                     span,
-                                    TypeErrorKind::Mismatch {
-                                        expected: "non-void return type".to_string(),
-                                        found: "void".to_string(),
-                                    },
+                                    TypeErrorKind::VoidInExpression,
                                 )),
                             }
                         }
@@ -350,6 +599,7 @@ fn typecheck_exp(h: &TypeCtxt, e: &ast::Node<ast::SExp>) -> TcResult<ast::STy> {
                         )),
                     }
                 }
+                Ty::TNullRef(_) => Err(null_use_error("call", &ftyp, f.loc.clone())),
                 _ => Err(type_error(
                     "Need function argument for function call",
                     f.loc.clone(),
@@ -389,10 +639,207 @@ fn typecheck_exp(h: &TypeCtxt, e: &ast::Node<ast::SExp>) -> TcResult<ast::STy> {
 //         not return.
 
 //         looping constructs never definitely return (While, For)
+
+//         break and continue never definitely return, and make any return
+//         after them in the same block unreachable
+//
+// Local variable declaration, shared by Decl statements and For loop headers
+//   var x = e;   x gets the type of e
+//   T x = e;     e must be a subtype of T, x gets T
+//   T x;         x starts at T's default value, so T needs one (int, bool, R?)
+// Redeclaring a variable of the current scope is an error (shadowing is fine).
 //
+// x is declared even when the declaration has an error, so that its uses
+// don't all report it as unbound: with T if T is well-formed, else with the
+// poison type.
+fn typecheck_vdecl(h: &mut TypeCtxt, vdecl: &ast::VDecl, loc: &common::Span) {
+    // ex. int x = 2;
+    //     string x = "hey";
+    if h.is_declared_in_current_scope(vdecl.vd_id) {
+        h.report(type_error(
+            "Cannot redeclare variable",
+            loc.clone(),
+            TypeErrorKind::Redeclaration {
+                name: vdecl.vd_id.to_string(),
+            },
+        ));
+    }
+
+    let ty = match typecheck_vdecl_ty(h, vdecl, loc) {
+        Ok(ty) => ty,
+        Err(e) => {
+            h.report(e);
+            match &vdecl.vd_ty {
+                Some(t) if typecheck_ty(h, t).is_ok() => t.node.clone(),
+                _ => t_poison(loc.clone()),
+            }
+        }
+    };
+    h.add_local(vdecl.vd_id, ty);
+}
+
+// the type a local declaration gives its variable
+fn typecheck_vdecl_ty(h: &mut TypeCtxt, vdecl: &ast::VDecl, loc: &common::Span) -> TcResult<Ty> {
+    let ty = match (&vdecl.vd_ty, &vdecl.vd_node) {
+        // var x = e;
+        (None, Some(exp_node)) => typecheck_exp(h, exp_node)?.node,
+
+        // T x = e;
+        (Some(t), Some(exp_node)) => {
+            typecheck_ty(h, t)?;
+            let exp_type = typecheck_exp(h, exp_node)?;
+            if !subtype(h, &exp_type, t) {
+                return Err(type_error(
+                    mismatch_msg(h, "Mismatched types in declaration", &exp_type, t),
+                    loc.clone(),
+                    TypeErrorKind::Mismatch {
+                        expected: format!("{:?}", t.node),
+                        found: format!("{:?}", exp_type.node),
+                    },
+                ));
+            }
+            t.node.clone()
+        }
+
+        // T x;
+        (Some(t), None) => {
+            typecheck_ty(h, t)?;
+            if t.node.default_exp().is_none() {
+                return Err(type_error(
+                    format!(
+                        "{} must be initialized: its type has no default value",
+                        vdecl.vd_id
+                    ),
+                    loc.clone(),
+                    TypeErrorKind::NoDefaultValue {
+                        ty: format!("{:?}", t.node),
+                    },
+                ));
+            }
+            t.node.clone()
+        }
+
+        // var x; (the parser never produces this)
+        (None, None) => {
+            return Err(type_error(
+                "Variable declaration needs a type or an initializer",
+                loc.clone(),
+                TypeErrorKind::Mismatch {
+                    expected: "initializer expression".to_string(),
+                    found: "none".to_string(),
+                },
+            ));
+        }
+    };
+    Ok(ty)
+}
+
+// the guard of an if, while, for or ?:, which must be a bool
+fn typecheck_guard(h: &mut TypeCtxt, guard: &ast::Node<ast::SExp>) -> TcResult<()> {
+    let guard_type = typecheck_exp(h, guard)?;
+    if guard_type.node != Ty::TBool {
+        return Err(type_error(
+            "Incorrect type for guard",
+            guard.loc.clone(),
+            TypeErrorKind::Mismatch {
+                expected: "TBool".to_string(),
+                found: format!("{:?}", guard_type.node),
+            },
+        ));
+    }
+    Ok(())
+}
+
+// the header of `if? (r x = e)`: e : r'? (or r') with r' <: r
+fn typecheck_cast(h: &mut TypeCtxt, r: &ast::SRefTy, e: &ast::Node<ast::SExp>) -> TcResult<()> {
+    typecheck_ref(h, r)?;
+    let e_type = typecheck_exp(h, e)?;
+
+    let narrows = match &e_type.node {
+        Ty::TNullRef(r2) | Ty::TRef(r2) => subtype_ref(h, r2, r),
+        _ => false,
+    };
+    if !narrows {
+        return Err(type_error(
+            "Mismatched types in if? declaration",
+            e.loc.clone(),
+            TypeErrorKind::Mismatch {
+                expected: format!("{:?}", Ty::TNullRef(r.clone())),
+                found: format!("{:?}", e_type.node),
+            },
+        ));
+    }
+    Ok(())
+}
+
+// the element type of the array a for-each loop iterates over
+fn typecheck_foreach(h: &mut TypeCtxt, arr: &ast::Node<ast::SExp>) -> TcResult<ast::STy> {
+    let arr_t = typecheck_exp(h, arr)?;
+    match &arr_t.node {
+        Ty::TRef(r) => match &r.node {
+            RefTy::RArray(t) => Ok((**t).clone()),
+            _ => Err(type_error(
+                format!("Tried to iterate over type {:?}", arr_t.node),
+                arr.loc.clone(),
+                TypeErrorKind::NotIndexable {
+                    ty: format!("{:?}", arr_t.node),
+                },
+            )),
+        },
+        Ty::TNullRef(_) => Err(null_use_error("iterate over", &arr_t, arr.loc.clone())),
+        _ => Err(type_error(
+            format!("Tried to iterate over type {:?}", arr_t.node),
+            arr.loc.clone(),
+            TypeErrorKind::NotIndexable {
+                ty: format!("{:?}", arr_t.node),
+            },
+        )),
+    }
+}
+
+// the target of an assignment (plain or compound) at statement `loc`, and its type
+fn typecheck_lhs(
+    h: &mut TypeCtxt,
+    e1: &ast::Node<ast::SExp>,
+    loc: &common::Span,
+) -> TcResult<ast::STy> {
+    // only variables, array elements and struct fields can be assigned
+    if !matches!(
+        &e1.elt.node,
+        Exp::Id(_) | Exp::Index(_, _) | Exp::Proj(_, _)
+    ) {
+        return Err(type_error(
+            "Left-hand side of assignment is not an lvalue",
+            e1.loc.clone(),
+            TypeErrorKind::InvalidLvalue,
+        ));
+    }
+
+    // Special check: prevent assignment to global functions
+    //   ex: foo = 5; where foo() is a global function -> ERROR...
+    //   else everything is OK!
+    if let Exp::Id(x) = &e1.elt.node {
+        // If x is NOT a local variable
+        if h.lookup_local_option(*x).is_none() {
+            // Check if x is a global function
+            if let Some(Ty::TRef(r)) = h.lookup_global_option(*x)
+                && matches!(&r.node, RefTy::RFun(..))
+            {
+                return Err(type_error(
+                    format!("cannot assign to global function {}", x),
+                    loc.clone(),
+                    TypeErrorKind::InvalidLvalue,
+                ));
+            }
+        }
+    }
+
+    typecheck_exp(h, e1)
+}
+
 // Example statements:
 //   - Assn: x = 5;
-//   - Decl: var x = 5;
+//   - Decl: var x = 5; or int x = 5; or int x;
 //   - Ret: return; or return x;
 //   - SCall: foo();
 //   - If: if (x) { ... } else { ... }
@@ -407,38 +854,17 @@ fn typecheck_stmt(
     to_ret: &ast::SRetTy,
 ) -> TcResult<bool> {
     match &s.elt.node {
-        // Assn: x = 5;
+        // Assn: x = 5; or a[i] = 5; or p.x = 5;
         Stmt::Assn(e1, e2) => {
-            // Special check: prevent assignment to global functions
-            //   ex: foo = 5; where foo() is a global function -> ERROR...
-            //   else everything is OK!
-            if let Exp::Id(x) = &e1.elt.node {
-                // If x is NOT a local variable
-                if h.lookup_local_option(x.as_str()).is_none() {
-                    // Check if x is a global function
-                    if let Some(Ty::TRef(r)) = h.lookup_global_option(x.as_str()) {
-                        if matches!(&r.node, RefTy::RFun(..)) {
-                            return Err(type_error(
-                                format!("cannot assign to global function {}", x),
-                                s.loc.clone(),
-                                TypeErrorKind::Mismatch {
-                                    expected: "assignable lvalue".to_string(),
-                                    found: "function".to_string(),
-                                },
-                            ));
-                        }
-                    }
-                }
-            }
-
-            // Typecheck both sides and ensure types match
-            let assn_to = typecheck_exp(h, e1)?; // STY
-            let assn_from = typecheck_exp(h, e2)?; // STy
+            // Typecheck both sides (both, even if one fails) and ensure types match
+            let assn_to = typecheck_lhs(h, e1, &s.loc);
+            let assn_from = typecheck_exp(h, e2);
+            let (assn_to, assn_from) = (assn_to?, assn_from?); // STy, STy
             if subtype(h, &assn_from, &assn_to) {
                 Ok(false) // Assignment doesn't definitely return
             } else {
                 Err(type_error(
-                    "Mismatched types in assignment",
+                    mismatch_msg(h, "Mismatched types in assignment", &assn_from, &assn_to),
                     s.loc.clone(),
                     TypeErrorKind::Mismatch {
                         expected: format!("{:?}", assn_to.node),
@@ -448,44 +874,66 @@ fn typecheck_stmt(
             }
         }
 
-        // Decl: int x = 5; where id is x and exp is
-        Stmt::Decl(vdecl) => {
-            // Check if variable is already declared in the current scope
-            // (not parent scopes - we allow shadowing)
-            // ex. int x = 2;
-            //     string x = "hey";
-            if h.is_declared_in_current_scope(&vdecl.vd_id) {
+        // lhs op= e: lhs : t and e : t' where op : t * t' -> t
+        // Example: x += 1; (int), done |= a[i] == 0; (bool)
+        Stmt::OpAssn(e1, b, e2) => {
+            let assn_to = typecheck_lhs(h, e1, &s.loc);
+            let assn_from = typecheck_exp(h, e2);
+            let (assn_to, assn_from) = (assn_to?, assn_from?);
+            let (bl, br, bres) = typ_of_binop(b);
+            if bres != bl {
                 return Err(type_error(
-                    "Cannot redeclare variable",
+                    format!("{}= is not a compound assignment operator", b),
                     s.loc.clone(),
-                    TypeErrorKind::UnknownIdentifier {
-                        name: vdecl.vd_id.clone(),
+                    TypeErrorKind::Mismatch {
+                        expected: format!("{:?}", bl),
+                        found: format!("{:?}", bres),
                     },
                 ));
             }
-            // VDecl can be with or without initializer:
-            match &vdecl.vd_node {
-                // var x; (uninitialized - should we allow this?)
-                None => {
-                    // For now, this is an error in our type system
-                    // because we need to know the type
-                    return Err(type_error(
-                        "Variable declaration without initializer not supported",
-                        s.loc.clone(),
-                        TypeErrorKind::Mismatch {
-                            expected: "initializer expression".to_string(),
-                            found: "none".to_string(),
-                        },
-                    ));
-                }
+            if assn_to.node != bl {
+                return Err(type_error(
+                    "Incorrect type in compound assignment",
+                    e1.loc.clone(),
+                    TypeErrorKind::Mismatch {
+                        expected: format!("{:?}", bl),
+                        found: format!("{:?}", assn_to.node),
+                    },
+                ));
+            }
+            if assn_from.node != br {
+                return Err(type_error(
+                    "Incorrect type in compound assignment",
+                    e2.loc.clone(),
+                    TypeErrorKind::Mismatch {
+                        expected: format!("{:?}", br),
+                        found: format!("{:?}", assn_from.node),
+                    },
+                ));
+            }
+            Ok(false)
+        }
 
-                // var x = e; (with initializer)
-                Some(exp_node) => {
-                    let exp_type = typecheck_exp(h, exp_node)?;
-                    h.add_local(vdecl.vd_id.clone(), exp_type.node);
-                    Ok(false) // Declaration doesn't definitely return
-                }
+        // lhs++ and lhs--: lhs : int
+        Stmt::Incr(e1) | Stmt::Decr(e1) => {
+            let t = typecheck_lhs(h, e1, &s.loc)?;
+            if t.node != Ty::TInt {
+                return Err(type_error(
+                    "Incorrect type for ++ or --",
+                    e1.loc.clone(),
+                    TypeErrorKind::Mismatch {
+                        expected: "TInt".to_string(),
+                        found: format!("{:?}", t.node),
+                    },
+                ));
             }
+            Ok(false)
+        }
+
+        // Decl: var x = 5; or int x = 5; or int x;
+        Stmt::Decl(vdecl) => {
+            typecheck_vdecl(h, vdecl, &s.loc);
+            Ok(false) // Declaration doesn't definitely return
         }
 
         // Return Statement:
@@ -501,7 +949,7 @@ fn typecheck_stmt(
                     Ok(true) // Return statement definitely returns
                 } else {
                     Err(type_error(
-                        "Returned incorrect type",
+                        mismatch_msg(h, "Returned incorrect type", &t, expected_ty),
                         s.loc.clone(),
                         TypeErrorKind::Mismatch {
                             expected: format!("{:?}", expected_ty.node),
@@ -535,17 +983,16 @@ fn typecheck_stmt(
         // foo(); (statement call - function must return void)
         // Example: print_int(42);
         Stmt::SCall(f, args) => {
-            let mut argtyps = Vec::new();
-            for arg in args {
-                argtyps.push(typecheck_exp(h, arg)?);
-            }
-
-            let ftyp = typecheck_exp(h, f)?;
+            let argtyps = typecheck_exps(h, args);
+            let ftyp = typecheck_exp(h, f);
+            let (argtyps, ftyp) = (argtyps?, ftyp?);
 
             match &ftyp.node {
-                Ty::TRef(r) | Ty::TNullRef(r) => {
+                Ty::TRef(r) => {
                     match &r.node {
                         RefTy::RFun(param_types, ret_ty) => {
+                            h.record_call(s, param_types, ret_ty);
+
                             // Check that function returns void
                             if !matches!(&ret_ty.node, RetTy::RetVoid) {
                                 return Err(type_error(
@@ -563,9 +1010,9 @@ fn typecheck_stmt(
                                 return Err(type_error(
                                     "Incorrect number of arguments",
                                     s.loc.clone(),
-                                    TypeErrorKind::Mismatch {
-                                        expected: format!("{} arguments", param_types.len()),
-                                        found: format!("{} arguments", argtyps.len()),
+                                    TypeErrorKind::ArityMismatch {
+                                        expected: param_types.len(),
+                                        found: argtyps.len(),
                                     },
                                 ));
                             }
@@ -576,7 +1023,12 @@ fn typecheck_stmt(
                             {
                                 if !subtype(h, arg, param) {
                                     return Err(type_error(
-                                        format!("Incorrect type of argument {}", i),
+                                        mismatch_msg(
+                                            h,
+                                            &format!("Incorrect type of argument {}", i),
+                                            arg,
+                                            param,
+                                        ),
                                         s.loc.clone(),
                                         TypeErrorKind::Mismatch {
                                             expected: format!("{:?}", param.node),
@@ -597,6 +1049,7 @@ fn typecheck_stmt(
                         )),
                     }
                 }
+                Ty::TNullRef(_) => Err(null_use_error("call", &ftyp, f.loc.clone())),
                 _ => Err(type_error(
                     "Need function argument for function call",
                     f.loc.clone(),
@@ -610,26 +1063,34 @@ fn typecheck_stmt(
         // if (e) { ... } else { ... } (conditional statement)
         // Example: if (x > 0) { return 1; } else { return -1; }
         Stmt::If(guard, then_block, else_block) => {
-            let guard_type = typecheck_exp(h, guard)?;
-
-            if guard_type.node != Ty::TBool {
-                return Err(type_error(
-                    "Incorrect type for guard",
-                    guard.loc.clone(),
-                    TypeErrorKind::Mismatch {
-                        expected: "TBool".to_string(),
-                        found: format!("{:?}", guard_type.node),
-                    },
-                ));
-            }
+            let guard_ok = typecheck_guard(h, guard);
+            h.check(guard_ok);
 
-            let lft_ret = typecheck_block(h, then_block, to_ret)?;
-            let rgt_ret = typecheck_block(h, else_block, to_ret)?;
+            let lft_ret = typecheck_block(h, then_block, to_ret);
+            let rgt_ret = typecheck_block(h, else_block, to_ret);
 
             // Both branches must return for the if to definitely return
             Ok(lft_ret && rgt_ret)
         }
 
+        // if? (r x = e) { ... } else { ... } (null-check narrowing)
+        // Example: if? (string s = maybe_s) { print_string(s); }
+        //      e : r'? (or r') with r' <: r
+        //      x : r, bound in the then-branch only
+        Stmt::Cast(r, x, e, then_block, else_block) => {
+            let narrows = typecheck_cast(h, r, e);
+            h.check(narrows);
+
+            // the then-branch gets its own scope holding x
+            h.push_scope();
+            h.add_local(*x, Ty::TRef(r.clone()));
+            let lft_ret = typecheck_block(h, then_block, to_ret);
+            h.pop_scope();
+            let rgt_ret = typecheck_block(h, else_block, to_ret);
+
+            Ok(lft_ret && rgt_ret)
+        }
+
         // for (var x = e1, var y = e2, ...; guard; increment) { body }
         // Example: for (var i = 0; i < 10; i = i + 1;) { print_int(i); }
         Stmt::For(vdecls, guard, increment, body) => {
@@ -638,60 +1099,35 @@ fn typecheck_stmt(
 
             // Add all declared variables to the new scope
             for vdecl in vdecls {
-                // Check the initializer if present
-                match &vdecl.vd_node {
-                    None => {
-                        h.pop_scope();
-                        return Err(type_error(
-                            "For loop variable declaration requires initializer",
-                            s.loc.clone(),
-                            TypeErrorKind::Mismatch {
-                                expected: "initializer".to_string(),
-                                found: "none".to_string(),
-                            },
-                        ));
-                    }
-                    Some(exp_node) => {
-                        let t = typecheck_exp(h, exp_node)?;
-                        h.add_local(vdecl.vd_id.clone(), t.node);
-                    }
-                }
+                typecheck_vdecl(h, vdecl, &s.loc);
             }
 
             // Check guard expression if present
             if let Some(guard_exp) = guard {
-                let guard_type = typecheck_exp(h, guard_exp)?;
-                if guard_type.node != Ty::TBool {
-                    h.pop_scope();
-                    return Err(type_error(
-                        "Incorrect type for guard",
-                        guard_exp.loc.clone(),
-                        TypeErrorKind::Mismatch {
-                            expected: "TBool".to_string(),
-                            found: format!("{:?}", guard_type.node),
-                        },
-                    ));
-                }
+                let guard_ok = typecheck_guard(h, guard_exp);
+                h.check(guard_ok);
             }
 
             // Check increment statement if present
             if let Some(inc_stmt) = increment {
-                let rt = typecheck_stmt(h, inc_stmt, to_ret)?;
-                if rt {
-                    h.pop_scope();
-                    return Err(type_error(
+                match typecheck_stmt(h, inc_stmt, to_ret) {
+                    Ok(false) => {}
+                    Ok(true) => h.report(type_error(
                         "Cannot return in for loop increment",
                         inc_stmt.loc.clone(),
                         TypeErrorKind::Mismatch {
                             expected: "non-returning statement".to_string(),
                             found: "return statement".to_string(),
                         },
-                    ));
+                    )),
+                    Err(e) => h.report(e),
                 }
             }
 
             // Typecheck the body
-            let _ = typecheck_block(h, body, to_ret)?;
+            h.enter_loop();
+            typecheck_block(h, body, to_ret);
+            h.exit_loop();
 
             // Pop the for loop scope
             h.pop_scope();
@@ -703,22 +1139,55 @@ fn typecheck_stmt(
         // while (e) { body } (while loop)
         // Example: while (x > 0) { x = x - 1; }
         Stmt::While(guard, body) => {
-            let guard_type = typecheck_exp(h, guard)?;
+            let guard_ok = typecheck_guard(h, guard);
+            h.check(guard_ok);
+
+            h.enter_loop();
+            typecheck_block(h, body, to_ret);
+            h.exit_loop();
+
+            // While loops never definitely return
+            Ok(false)
+        }
+
+        // for (x in e) { body } (for-each loop)
+        // Example: for (p in points) { sum = sum + p.x; }
+        //      e : t[] (not t[]?), x : t in a fresh scope around the body
+        Stmt::ForEach(x, arr, body) => {
+            // x still gets a type when e is not an array, for the sake of the body
+            let elt_t = typecheck_foreach(h, arr);
+            let elt_t = h
+                .check(elt_t)
+                .map_or_else(|| t_poison(arr.loc.clone()), |t| t.node);
+
+            h.push_scope();
+            h.add_local(*x, elt_t);
+            h.enter_loop();
+            typecheck_block(h, body, to_ret);
+            h.exit_loop();
+            h.pop_scope();
 
-            if guard_type.node != Ty::TBool {
+            // the array may be empty, so the loop never definitely returns
+            Ok(false)
+        }
+
+        // break; or continue; (only inside a loop body)
+        // control leaves the block, so neither definitely returns
+        Stmt::Break | Stmt::Continue => {
+            if !h.in_loop() {
+                let kw = if matches!(s.elt.node, Stmt::Break) {
+                    "break"
+                } else {
+                    "continue"
+                };
                 return Err(type_error(
-                    "Incorrect type for guard",
-                    guard.loc.clone(),
-                    TypeErrorKind::Mismatch {
-                        expected: "TBool".to_string(),
-                        found: format!("{:?}", guard_type.node),
+                    format!("{} outside of a loop", kw),
+                    s.loc.clone(),
+                    TypeErrorKind::NotInLoop {
+                        stmt: kw.to_string(),
                     },
                 ));
             }
-
-            let _ = typecheck_block(h, body, to_ret)?;
-
-            // While loops never definitely return
             Ok(false)
         }
     }
@@ -730,15 +1199,28 @@ fn typecheck_block(
     h: &mut TypeCtxt,
     block: &[ast::Node<ast::SStmt>],
     to_ret: &ast::SRetTy,
-) -> TcResult<bool> {
+) -> bool {
     // Push a new scope for the block
     h.push_scope();
 
     let mut definitely_returns = false;
+    // a return after break/continue is unreachable, so it doesn't count
+    let mut jumped = false;
 
     for stmt in block {
-        let returns = typecheck_stmt(h, stmt, to_ret)?;
-        if returns {
+        let returns = match typecheck_stmt(h, stmt, to_ret) {
+            Ok(returns) => returns,
+            // a return with an error in it still returns, so the function
+            // isn't also reported as missing one
+            Err(e) => {
+                h.report(e);
+                matches!(stmt.elt.node, Stmt::Ret(_))
+            }
+        };
+        if matches!(stmt.elt.node, Stmt::Break | Stmt::Continue) {
+            jumped = true;
+        }
+        if returns && !jumped {
             definitely_returns = true;
             // for now, we will ignore unreachable code,
             // once every block "returns" wether they are reachable or not,
@@ -750,29 +1232,31 @@ fn typecheck_block(
     // Pop the block scope
     h.pop_scope();
 
-    Ok(definitely_returns)
+    definitely_returns
 }
 
 // PROGRAM-LEVEL TYPECHECKING -------------------------------------------
 
 // Typecheck a global variable declaration ---------------------------
 //      ex: int global_x = 42;
-fn typecheck_gvdecl(h: &mut TypeCtxt, gvdecl: &ast::Node<ast::GDecl>) -> TcResult<()> {
+fn typecheck_gvdecl(h: &mut TypeCtxt, gvdecl: &ast::Node<ast::GDecl>) {
     let name = &gvdecl.elt.name;
     let init = &gvdecl.elt.init;
 
     // Typecheck the initializer expression
-    let init_type = typecheck_exp(h, init)?;
-
-    // Add to global context
-    h.add_global(name.clone(), init_type.node);
-
-    Ok(())
+    let init_type = typecheck_exp(h, init);
+
+    // Add to global context (with the poison type if the initializer is
+    // ill-typed, as for locals)
+    let ty = h
+        .check(init_type)
+        .map_or_else(|| t_poison(init.loc.clone()), |t| t.node);
+    h.add_global(*name, ty);
 }
 
 // Typecheck a struct/type declaration -------------------------------
 //      ex : struct Point { int x; int y; }
-fn typecheck_tdecl(h: &mut TypeCtxt, tdecl: &ast::Node<ast::TDecl>) -> TcResult<()> {
+fn typecheck_tdecl(h: &mut TypeCtxt, tdecl: &ast::Node<ast::TDecl>) {
     let struct_name = &tdecl.elt.td_id;
     let fields = &tdecl.elt.td_node;
 
@@ -780,26 +1264,27 @@ fn typecheck_tdecl(h: &mut TypeCtxt, tdecl: &ast::Node<ast::TDecl>) -> TcResult<
     let mut field_names = std::collections::HashSet::new();
     for field in fields {
         if !field_names.insert(&field.field_name) {
-            return Err(type_error(
+            h.report(type_error(
                 format!(
                     "Duplicate field name '{}' in struct '{}'",
                     field.field_name, struct_name
                 ),
                 tdecl.loc.clone(),
-                TypeErrorKind::UnknownIdentifier {
-                    name: field.field_name.clone(),
+                TypeErrorKind::DuplicateField {
+                    struct_name: struct_name.to_string(),
+                    field: field.field_name.to_string(),
                 },
             ));
         }
 
         // check field ty is well formed
         let field_ty = mk_sty(field.field_type.clone(), tdecl.loc.clone());
-        typecheck_ty(h, &field_ty)?;
+        let well_formed = typecheck_ty(h, &field_ty);
+        h.check(well_formed);
     }
 
-    h.add_struct(struct_name.clone(), fields.clone());
-
-    Ok(())
+    // added even with errors, so its uses don't report it as unbound
+    h.add_struct(*struct_name, fields.clone());
 }
 
 // Typecheck a function declaration ------------------------------------
@@ -821,16 +1306,16 @@ fn typecheck_fdecl(h: &mut TypeCtxt, fdecl: &ast::Node<ast::FDecl>) -> TcResult<
     );
     let fun_ty = mk_sty(Ty::TRef(fun_ref_ty), fdecl.loc.clone());
 
-    h.add_global(fname.clone(), fun_ty.node);
+    h.add_global(*fname, fun_ty.node);
 
     // new scope for function body
     h.push_scope();
 
     for arg in args {
-        h.add_local(arg.id.clone(), arg.ty.node.clone());
+        h.add_local(arg.id, arg.ty.node.clone());
     }
 
-    let body_returns = typecheck_block(h, body, &ret_ty_spanned)?;
+    let body_returns = typecheck_block(h, body, &ret_ty_spanned);
 
     // Check that non-void functions return on all paths
     if !matches!(ret_ty, RetTy::RetVoid) && !body_returns {
@@ -838,9 +1323,8 @@ fn typecheck_fdecl(h: &mut TypeCtxt, fdecl: &ast::Node<ast::FDecl>) -> TcResult<
         return Err(type_error(
             format!("Function '{}' does not return on all paths", fname),
             fdecl.loc.clone(),
-            TypeErrorKind::Mismatch {
-                expected: "return statement".to_string(),
-                found: "end of function".to_string(),
+            TypeErrorKind::MissingReturn {
+                name: fname.to_string(),
             },
         ));
     }
@@ -853,15 +1337,17 @@ fn typecheck_fdecl(h: &mut TypeCtxt, fdecl: &ast::Node<ast::FDecl>) -> TcResult<
 // Typecheck an entire program -------------------------------
 //      Remember: a Prog is a Vec of Decl
 //      and Decl: global variables, functions, and structs
-// Public entry point for typechecking a program
-pub fn typecheck_prog(prog: &ast::Prog) -> TcResult<()> {
+//
+// Every error is reported: after one, checking goes on with the next
+// statement or declaration (see TcError and the poison type in lib.rs)
+pub fn typecheck_prog_all(prog: &ast::Prog) -> Result<TypeTable, Vec<TypeError>> {
     let mut h = TypeCtxt::empty();
 
     // Two-pass approach:
     // Pass 1: Add all struct declarations first (so functions can reference them)
     for decl in prog {
         if let ast::Decl::GTDecl(tdecl) = decl {
-            typecheck_tdecl(&mut h, tdecl)?;
+            typecheck_tdecl(&mut h, tdecl);
         }
     }
 
@@ -869,10 +1355,11 @@ pub fn typecheck_prog(prog: &ast::Prog) -> TcResult<()> {
     for decl in prog {
         match decl {
             ast::Decl::GVDecl(gvdecl) => {
-                typecheck_gvdecl(&mut h, gvdecl)?;
+                typecheck_gvdecl(&mut h, gvdecl);
             }
             ast::Decl::GFDecl(fdecl) => {
-                typecheck_fdecl(&mut h, fdecl)?;
+                let checked = typecheck_fdecl(&mut h, fdecl);
+                h.check(checked);
             }
             ast::Decl::GTDecl(_) => {
                 // Already processed in pass 1
@@ -880,5 +1367,15 @@ pub fn typecheck_prog(prog: &ast::Prog) -> TcResult<()> {
         }
     }
 
-    Ok(())
+    let (types, errors) = h.into_results();
+    if errors.is_empty() {
+        Ok(types)
+    } else {
+        Err(errors)
+    }
+}
+
+// Public entry point for typechecking a program: the first error, in source order
+pub fn typecheck_prog(prog: &ast::Prog) -> Result<TypeTable, TypeError> {
+    typecheck_prog_all(prog).map_err(|errors| errors.into_iter().next().unwrap())
 }
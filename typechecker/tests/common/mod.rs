@@ -1,8 +1,21 @@
 // Test helper functions for building AST nodes quickly
+#![allow(dead_code)]
 
 use ast::*;
 use common::{Span, Spanned};
 
+// Parse Oat source, panicking on any syntax error
+pub fn parse(src: &str) -> Prog {
+    let (prog, errs) = parser::parse_prog(src);
+    assert!(errs.is_empty(), "syntax errors in test program: {:?}", errs);
+    prog
+}
+
+// Parse a single Oat expression
+pub fn parse_exp(src: &str) -> Node<SExp> {
+    parser::parse_exp(src).expect("syntax error in test expression")
+}
+
 // Helper to create a dummy span
 pub fn dummy_span() -> Span {
     Span::new(0, 0)
@@ -13,6 +26,7 @@ pub fn node<T>(elt: T) -> Node<T> {
     Node {
         elt,
         loc: dummy_span(),
+        id: NodeId::DUMMY,
     }
 }
 
@@ -47,12 +61,12 @@ pub fn r_array(ty: Ty) -> RefTy {
 }
 
 pub fn r_fun(args: Vec<Ty>, ret: RetTy) -> RefTy {
-    let spanned_args = args.into_iter().map(|t| spanned(t)).collect();
+    let spanned_args = args.into_iter().map(spanned).collect();
     RefTy::RFun(spanned_args, Box::new(spanned(ret)))
 }
 
 pub fn r_struct(name: &str) -> RefTy {
-    RefTy::RStruct(name.to_string())
+    RefTy::RStruct(name.into())
 }
 
 pub fn ret_void() -> RetTy {
@@ -77,7 +91,7 @@ pub fn e_str(s: &str) -> Node<SExp> {
 }
 
 pub fn e_id(name: &str) -> Node<SExp> {
-    node(spanned(Exp::Id(name.to_string())))
+    node(spanned(Exp::Id(name.into())))
 }
 
 pub fn e_null(rty: RefTy) -> Node<SExp> {
@@ -111,11 +125,20 @@ pub fn s_assn(lhs: Node<SExp>, rhs: Node<SExp>) -> Node<SStmt> {
 
 pub fn s_decl(name: &str, init: Node<SExp>) -> Node<SStmt> {
     node(spanned(Stmt::Decl(VDecl {
-        vd_id: name.to_string(),
+        vd_id: name.into(),
+        vd_ty: None,
         vd_node: Some(init),
     })))
 }
 
+pub fn s_decl_ty(name: &str, ty: Ty, init: Option<Node<SExp>>) -> Node<SStmt> {
+    node(spanned(Stmt::Decl(VDecl {
+        vd_id: name.into(),
+        vd_ty: Some(spanned(ty)),
+        vd_node: init,
+    })))
+}
+
 pub fn s_ret(expr: Option<Node<SExp>>) -> Node<SStmt> {
     node(spanned(Stmt::Ret(expr)))
 }
@@ -135,7 +158,7 @@ pub fn s_while(guard: Node<SExp>, body: Vec<Node<SStmt>>) -> Node<SStmt> {
 // Declaration constructors
 pub fn d_gvar(name: &str, init: Node<SExp>) -> Decl {
     Decl::GVDecl(node(GDecl {
-        name: name.to_string(),
+        name: name.into(),
         init,
     }))
 }
@@ -145,13 +168,13 @@ pub fn d_func(name: &str, args: Vec<(&str, Ty)>, ret: RetTy, body: Vec<Node<SStm
         .into_iter()
         .map(|(id, ty)| Arg {
             ty: spanned(ty),
-            id: id.to_string(),
+            id: id.into(),
         })
         .collect();
 
     Decl::GFDecl(node(FDecl {
         fret_ty: ret,
-        fname: name.to_string(),
+        fname: name.into(),
         args,
         body,
     }))
@@ -161,13 +184,13 @@ pub fn d_struct(name: &str, fields: Vec<(&str, Ty)>) -> Decl {
     let fields = fields
         .into_iter()
         .map(|(name, ty)| Field {
-            field_name: name.to_string(),
+            field_name: name.into(),
             field_type: ty,
         })
         .collect();
 
     Decl::GTDecl(node(TDecl {
-        td_id: name.to_string(),
+        td_id: name.into(),
         td_node: fields,
     }))
 }
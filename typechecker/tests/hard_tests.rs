@@ -2,6 +2,7 @@
 
 #[path = "common/mod.rs"]
 mod common;
+use ::common::TypeErrorKind;
 use ast::*;
 use common::*;
 use typechecker::typecheck_prog;
@@ -130,7 +131,12 @@ fn test_type_error_missing_return() {
         body,
     )];
 
-    assert!(typecheck_prog(&prog).is_err());
+    let err = typecheck_prog(&prog).unwrap_err();
+    assert!(
+        matches!(err.kind, TypeErrorKind::MissingReturn { .. }),
+        "{:?}",
+        err.kind
+    );
 }
 
 #[test]
@@ -143,7 +149,12 @@ fn test_type_error_duplicate_variable() {
     let body = vec![s_decl("x", e_int(5)), s_decl("x", e_int(10))];
     let prog = vec![d_func("duplicate", vec![], ret_void(), body)];
 
-    assert!(typecheck_prog(&prog).is_err());
+    let err = typecheck_prog(&prog).unwrap_err();
+    assert!(
+        matches!(err.kind, TypeErrorKind::Redeclaration { .. }),
+        "{:?}",
+        err.kind
+    );
 }
 
 #[test]
@@ -196,3 +207,49 @@ fn test_complex_nested_scopes() {
 
     assert!(typecheck_prog(&prog).is_ok());
 }
+
+#[test]
+fn test_typed_declaration_with_subtype_initializer() {
+    // int[]? a = new int[3];   (int[] <: int[]?)
+    // bool b;                  (defaults to false)
+    let body = vec![
+        s_decl_ty(
+            "a",
+            t_null_ref(r_array(t_int())),
+            Some(e_new_arr(t_int(), e_int(3))),
+        ),
+        s_decl_ty("b", t_bool(), None),
+        s_ret(None),
+    ];
+    let prog = vec![d_func("foo", vec![], ret_void(), body)];
+
+    assert!(typecheck_prog(&prog).is_ok());
+}
+
+#[test]
+fn test_nullable_not_subtype_of_non_null() {
+    // string f(string? s) { return s; }
+    let prog = vec![d_func(
+        "f",
+        vec![("s", t_null_ref(r_string()))],
+        ret_val(t_ref(r_string())),
+        vec![s_ret(Some(e_id("s")))],
+    )];
+
+    let err = typecheck_prog(&prog).unwrap_err();
+    assert!(err.msg.contains("if?"), "no hint in: {}", err.msg);
+}
+
+#[test]
+fn test_call_through_nullable_function() {
+    // int f(((int) -> int)? g) { return g(1); }
+    let prog = vec![d_func(
+        "f",
+        vec![("g", t_null_ref(r_fun(vec![t_int()], ret_val(t_int()))))],
+        ret_val(t_int()),
+        vec![s_ret(Some(e_call(e_id("g"), vec![e_int(1)])))],
+    )];
+
+    let err = typecheck_prog(&prog).unwrap_err();
+    assert!(err.msg.contains("may be null"), "{}", err.msg);
+}
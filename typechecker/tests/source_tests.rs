@@ -0,0 +1,762 @@
+// Typechecker tests written as Oat source, parsed with the parser crate
+
+#[path = "common/mod.rs"]
+mod common;
+use ast::{EqIgnoringSpans, Exp, Node, NodeId, RetTy, SExp, SStmt, Stmt, Ty, Visitor, visit};
+use common::*;
+use typechecker::{typecheck_prog, typecheck_prog_all};
+
+#[test]
+fn test_arrays_and_loops() {
+    let prog = parse(
+        "int sum(int[] a, int n) {
+            var s = 0;
+            for (var i = 0; i < n; i = i + 1;) {
+                s = s + a[i];
+            }
+            return s;
+        }
+        int main() {
+            var a = new int[]{1, 2, 3};
+            return sum(a, 3);
+        }",
+    );
+
+    assert!(typecheck_prog(&prog).is_ok());
+}
+
+#[test]
+fn test_globals_and_nested_calls() {
+    let prog = parse(
+        "global limit = 10;
+        global greeting = \"hello\";
+        bool small(int x) { return x < limit; }
+        int twice(int x) { return x * 2; }
+        void main() {
+            if (small(twice(3)) & !false) {
+                var s = greeting;
+            }
+            return;
+        }",
+    );
+
+    assert!(typecheck_prog(&prog).is_ok());
+}
+
+#[test]
+fn test_global_initializers() {
+    // any expression over the globals declared before, and the functions
+    let prog = parse(
+        "struct P { int x; }
+        int f() { return 1; }
+        global neg = -1;
+        global arr = new int[]{-1, 2};
+        global p = new P { x = -3 };
+        global n = 1 + 2;
+        global a = new int[n];
+        global b = new int[]{1, -n, f()};
+        global x = f() + length(b);",
+    );
+    assert!(typecheck_prog(&prog).is_ok());
+
+    let err = typecheck_prog(&parse("global m = n; global n = 1;")).unwrap_err();
+    assert!(
+        matches!(&err.kind, ::common::TypeErrorKind::UnknownIdentifier { name } if name == "n"),
+        "{:?}",
+        err.kind
+    );
+}
+
+#[test]
+fn test_function_values() {
+    let prog = parse(
+        "int inc(int x) { return x + 1; }
+        int apply((int) -> int f, int x) { return f(x); }
+        int main() { return apply(inc, 41); }",
+    );
+
+    assert!(typecheck_prog(&prog).is_ok());
+}
+
+#[test]
+fn test_array_length() {
+    let prog = parse(
+        "global g = new bool[]{true};
+        int f(int[][] a) {
+            var n = length(a) + length(g);
+            for (var i = 0; i < length(a); i = i + 1;) {
+                n = n + length(a[i]);
+            }
+            return n;
+        }",
+    );
+
+    assert!(typecheck_prog(&prog).is_ok());
+}
+
+#[test]
+fn test_type_error_length_of_non_array() {
+    for src in [
+        "int f(string s) { return length(s); }",
+        "int f(int[]? a) { return length(a); }",
+        "int f() { return length(3); }",
+    ] {
+        assert!(typecheck_prog(&parse(src)).is_err(), "accepted: {}", src);
+    }
+}
+
+#[test]
+fn test_break_and_continue() {
+    let prog = parse(
+        "int f(int[] a) {
+            var s = 0;
+            for (var i = 0; i < length(a); i = i + 1;) {
+                if (a[i] < 0) { continue; }
+                while (true) { break; }
+                if (s > 100) { break; } else { s = s + a[i]; }
+            }
+            return s;
+        }",
+    );
+
+    assert!(typecheck_prog(&prog).is_ok());
+}
+
+#[test]
+fn test_type_error_break_outside_loop() {
+    for src in [
+        "void f() { break; }",
+        "void f() { if (true) { continue; } return; }",
+        // the loop is over by the time the next statement runs
+        "void f() { while (true) { } break; }",
+    ] {
+        assert!(typecheck_prog(&parse(src)).is_err(), "accepted: {}", src);
+    }
+}
+
+#[test]
+fn test_if_q_narrowing() {
+    let prog = parse(
+        "struct P { int x; }
+        int f(P? p, string? s) {
+            if? (P q = p) {
+                return q.x;
+            } else if? (string t = s) {
+                return 1;
+            } else {
+                return 0;
+            }
+        }
+        void g(int[] a) {
+            if? (int[] b = a) { b[0] = 1; }
+            return;
+        }",
+    );
+
+    assert!(typecheck_prog(&prog).is_ok());
+}
+
+#[test]
+fn test_type_error_if_q() {
+    for src in [
+        // not a reference
+        "void f(int x) { if? (string s = x) { } return; }",
+        // wrong reference type
+        "void f(int[]? a) { if? (string s = a) { } return; }",
+        // x is bound in the then-branch only
+        "struct P { int x; } int f(P? p) { if? (P q = p) { } else { return q.x; } return 0; }",
+        "struct P { int x; } int f(P? p) { if? (P q = p) { } return q.x; }",
+    ] {
+        assert!(typecheck_prog(&parse(src)).is_err(), "accepted: {}", src);
+    }
+}
+
+#[test]
+fn test_type_error_nullable_as_non_null() {
+    for src in [
+        "void f(string? s) { string t = s; return; }",
+        "void g(int[] a) { return; } void f(int[]? a) { g(a); return; }",
+        "struct P { int x; } void f(P? p) { var q = new P { x = 1 }; q = p; return; }",
+        "string f() { return string null; }",
+    ] {
+        let err = typecheck_prog(&parse(src)).unwrap_err();
+        assert!(
+            err.msg.contains("if?"),
+            "no hint for `{}`: {}",
+            src,
+            err.msg
+        );
+    }
+}
+
+#[test]
+fn test_type_error_use_of_nullable() {
+    for src in [
+        "int f(int[]? a) { return a[0]; }",
+        "void f(int[]? a) { a[0] = 1; return; }",
+        "int f(int[]? a) { return length(a); }",
+        "struct P { int x; } int f(P? p) { return p.x; }",
+    ] {
+        let err = typecheck_prog(&parse(src)).unwrap_err();
+        assert!(err.msg.contains("may be null"), "`{}`: {}", src, err.msg);
+        assert!(
+            err.msg.contains("if?"),
+            "no hint for `{}`: {}",
+            src,
+            err.msg
+        );
+    }
+}
+
+const SHAPES: &str = "
+    struct Point { int x; int y; }
+    struct Point3 { int x; int y; int z; }
+    struct Named { int x; int y; string name; }
+    struct Swapped { int y; int x; }
+    struct Wide { int x; bool y; }
+    int norm1(Point p) { return p.x + p.y; }
+";
+
+#[test]
+fn test_struct_width_subtyping() {
+    let prog = parse(&format!(
+        "{}
+        int f(Point3 q, Named n) {{
+            var s = norm1(q) + norm1(n);
+            Point p = q;
+            p = n;
+            Point? mp = q;
+            var ps = new Point[]{{q, n, p}};
+            return s + ps[0].x;
+        }}",
+        SHAPES
+    ));
+
+    assert!(typecheck_prog(&prog).is_ok());
+}
+
+#[test]
+fn test_type_error_invalid_upcast() {
+    for body in [
+        // supertype to subtype
+        "int f(Point p) { Point3 q = p; return 0; }",
+        "int g(Point3 q) { return q.z; } int f(Point p) { return g(p); }",
+        // same fields, different order
+        "int f(Swapped s) { return norm1(s); }",
+        "int f(Swapped s) { var p = new Point { x = 1; y = 2 }; p = s; return 0; }",
+        // same names, different field type
+        "int f(Wide w) { return norm1(w); }",
+        // nullable subtype is still nullable
+        "int f(Point3? q) { return norm1(q); }",
+        // arrays are invariant
+        "int f(Point3[] qs) { Point[] ps = qs; return 0; }",
+    ] {
+        let src = format!("{}\n{}", SHAPES, body);
+        assert!(typecheck_prog(&parse(&src)).is_err(), "accepted: {}", body);
+    }
+}
+
+#[test]
+fn test_for_each() {
+    let prog = parse(
+        "struct P { int x; }
+        int f(P[] ps, int[][] m) {
+            var s = 0;
+            for (p in ps) { s = s + p.x; }
+            for (row in m) {
+                for (x in row) {
+                    if (x < 0) { break; }
+                    s = s + x;
+                }
+            }
+            for (q in new P?[3]) {
+                if? (P r = q) { s = s + r.x; } else { continue; }
+            }
+            return s;
+        }",
+    );
+
+    assert!(typecheck_prog(&prog).is_ok());
+}
+
+#[test]
+fn test_type_error_for_each() {
+    for src in [
+        // not an array
+        "void f(string s) { for (c in s) { } return; }",
+        // nullable array
+        "void f(int[]? a) { for (x in a) { } return; }",
+        // x has the element type
+        "void f(int[] a) { for (x in a) { bool b = x; } return; }",
+        // x is scoped to the body
+        "int f(int[] a) { for (x in a) { } return x; }",
+    ] {
+        assert!(typecheck_prog(&parse(src)).is_err(), "accepted: {}", src);
+    }
+
+    // the body may not run at all
+    let prog = parse("int f(int[] a) { for (x in a) { return x; } }");
+    assert!(typecheck_prog(&prog).is_err());
+}
+
+#[test]
+fn test_type_error_condition_not_bool() {
+    let prog = parse("void f() { while (1) { } return; }");
+
+    assert!(typecheck_prog(&prog).is_err());
+}
+
+#[test]
+fn test_division() {
+    let prog = parse("int f(int a, int b) { return a / b + a % b * 2; }");
+    assert!(typecheck_prog(&prog).is_ok());
+
+    let prog = parse("int f(int a, bool b) { return a / b; }");
+    assert!(typecheck_prog(&prog).is_err());
+    let prog = parse("bool f(int a, int b) { return a % b; }");
+    assert!(typecheck_prog(&prog).is_err());
+}
+
+#[test]
+fn test_conditional() {
+    let prog = parse(
+        "struct P { int x; }
+        struct Q { int x; int y; }
+        int f(bool b, int i) { return b ? i : i + 1; }
+        P g(bool b, P p, Q q) { return b ? q : p; }
+        int h(bool b, Q q) { var p = b ? q : new P { x = 1 }; return p.x; }",
+    );
+    assert!(typecheck_prog(&prog).is_ok());
+
+    // nullable and non-null combine into the nullable type
+    let prog = parse(
+        "struct P { int x; }
+        struct Q { int x; int y; }
+        P? f(bool b, P p) { return b ? p : P null; }
+        P? g(bool b, Q? q, P p) { return b ? q : p; }",
+    );
+    assert!(typecheck_prog(&prog).is_ok());
+    let prog = parse("struct P { int x; } P f(bool b, P p) { return b ? p : P null; }");
+    assert!(typecheck_prog(&prog).is_err());
+}
+
+#[test]
+fn test_type_error_conditional() {
+    let bad = [
+        "int f(int i) { return i ? 1 : 2; }",
+        "int f(bool b) { return b ? 1 : true; }",
+        "string f(bool b, int[] a) { return b ? \"s\" : a; }",
+    ];
+    for src in bad {
+        assert!(typecheck_prog(&parse(src)).is_err(), "accepted: {}", src);
+    }
+}
+
+#[test]
+fn test_compound_assignment() {
+    let prog = parse(
+        "struct P { int n; bool ok; }
+        global total = 0;
+        void f(int[] a, P p, bool b) {
+            for (var i = 0; i < length(a); i++;) {
+                a[i] *= 2;
+                a[i] >>>= 1;
+                total += a[i];
+            }
+            p.n--;
+            p.ok &= b;
+            p.ok |= a[0] == 0;
+            return;
+        }",
+    );
+    assert!(typecheck_prog(&prog).is_ok());
+}
+
+#[test]
+fn test_type_error_compound_assignment() {
+    let bad = [
+        "void f(bool b) { b += 1; return; }",
+        "void f(int x) { x += true; return; }",
+        "void f(int x) { x &= true; return; }",
+        "void f(bool b) { b++; return; }",
+        "void f(string s) { s--; return; }",
+        "int g() { return 1; } void f() { g() += 1; return; }",
+        "void f(int x) { (x + 1)++; return; }",
+        "int g() { return 1; } void f() { g++; return; }",
+    ];
+    for src in bad {
+        assert!(typecheck_prog(&parse(src)).is_err(), "accepted: {}", src);
+    }
+}
+
+#[test]
+fn test_type_error_array_element() {
+    let prog = parse("void f() { var a = new int[]{1, true}; return; }");
+
+    assert!(typecheck_prog(&prog).is_err());
+}
+
+#[test]
+fn test_type_error_call_arity() {
+    let prog = parse(
+        "int add(int a, int b) { return a + b; }
+        int main() { return add(1); }",
+    );
+
+    assert!(typecheck_prog(&prog).is_err());
+}
+
+#[test]
+fn test_parsed_expression_matches_builders() {
+    // the builders use dummy spans, so compare up to them
+    let built = e_bop(ast::BinOp::Add, e_index(e_id("a"), e_id("i")), e_int(1));
+
+    assert_ne!(parse_exp("a[i] + 1"), built);
+    assert!(parse_exp("a[i] + 1").eq_ignoring_spans(&built));
+}
+
+#[test]
+fn test_typed_declarations() {
+    let prog = parse(
+        "struct P { int x; }
+        int f() {
+            int x = 5;
+            bool b;
+            string s = \"hi\";
+            string? ms;
+            P? p;
+            int[]? a = new int[3];
+            for (int i = 0, int j; i < x; i = i + 1;) {
+                x = x + j;
+            }
+            return x;
+        }",
+    );
+
+    assert!(typecheck_prog(&prog).is_ok());
+}
+
+#[test]
+fn test_type_error_declared_type_mismatch() {
+    let prog = parse("void f() { int x = true; return; }");
+
+    assert!(typecheck_prog(&prog).is_err());
+}
+
+#[test]
+fn test_type_error_no_default_value() {
+    // non-null references must be initialized
+    let prog = parse("void f() { string s; return; }");
+    assert!(typecheck_prog(&prog).is_err());
+
+    let prog = parse("void f() { for (int[] a; ; ) { } return; }");
+    assert!(typecheck_prog(&prog).is_err());
+}
+
+#[test]
+fn test_type_error_unknown_declared_type() {
+    let prog = parse("void f() { Q? q; return; }");
+
+    assert!(typecheck_prog(&prog).is_err());
+}
+
+#[test]
+fn test_struct_literals_and_projection() {
+    let prog = parse(
+        "struct P { int x; int y; }
+        struct L { P head; P? tail; }
+        int f() {
+            var p = new P { y = 2; x = 1 };
+            var l = new L { head = p; tail = P null };
+            l.head.x = l.head.y + p.x;
+            return l.head.x;
+        }",
+    );
+
+    assert!(typecheck_prog(&prog).is_ok());
+}
+
+#[test]
+fn test_type_error_struct_literal_fields() {
+    let bad = [
+        // missing field
+        "struct P { int x; int y; } void f() { var p = new P { x = 1 }; return; }",
+        // extra field
+        "struct P { int x; } void f() { var p = new P { x = 1; z = 2 }; return; }",
+        // duplicate field
+        "struct P { int x; } void f() { var p = new P { x = 1; x = 2 }; return; }",
+        // wrong field type
+        "struct P { int x; } void f() { var p = new P { x = true }; return; }",
+        // unknown struct
+        "void f() { var p = new Q { }; return; }",
+    ];
+    for src in bad {
+        assert!(typecheck_prog(&parse(src)).is_err(), "accepted: {}", src);
+    }
+}
+
+#[test]
+fn test_type_error_projection() {
+    let bad = [
+        "struct P { int x; } int f(P p) { return p.y; }",
+        "int f(int[] a) { return a.x; }",
+        "struct P { int x; } void f(P p) { p.x = false; return; }",
+    ];
+    for src in bad {
+        assert!(typecheck_prog(&parse(src)).is_err(), "accepted: {}", src);
+    }
+}
+
+#[test]
+fn test_type_error_assign_non_lvalue() {
+    let prog = parse("int g() { return 1; } void f() { g() = 2; return; }");
+
+    assert!(typecheck_prog(&prog).is_err());
+}
+
+// every statement and expression of a program, by id
+#[derive(Default)]
+struct Nodes {
+    stmts: Vec<(NodeId, Stmt)>,
+    exps: Vec<(NodeId, Exp)>,
+}
+
+impl Visitor for Nodes {
+    fn visit_stmt(&mut self, s: &Node<SStmt>) {
+        self.stmts.push((s.id, s.elt.node.clone()));
+        visit::walk_stmt(self, s);
+    }
+
+    fn visit_exp(&mut self, e: &Node<SExp>) {
+        self.exps.push((e.id, e.elt.node.clone()));
+        visit::walk_exp(self, e);
+    }
+}
+
+#[test]
+fn test_type_table() {
+    let prog = parse(
+        "int add(int a, int b) { return a + b; }
+        void show(bool b) { return; }
+        void f() { var big = add(1, 2) > 2; show(big); return; }",
+    );
+    let types = typecheck_prog(&prog).expect("typecheck failed");
+    let mut nodes = Nodes::default();
+    nodes.visit_prog(&prog);
+
+    // every expression has a type
+    for (id, e) in &nodes.exps {
+        assert!(types.exps.get_id(*id).is_some(), "no type for {:?}", e);
+    }
+    let ty_of = |want: fn(&Exp) -> bool| {
+        let (id, _) = nodes.exps.iter().find(|(_, e)| want(e)).unwrap();
+        types.exps.get_id(*id).unwrap().node.clone()
+    };
+    assert_eq!(
+        ty_of(|e| matches!(e, Exp::Bop(ast::BinOp::Gt, ..))),
+        Ty::TBool
+    );
+    assert_eq!(ty_of(|e| matches!(e, Exp::Call(..))), Ty::TInt);
+
+    // both calls, expression and statement, record the callee's type
+    let (call, _) = nodes
+        .exps
+        .iter()
+        .find(|(_, e)| matches!(e, Exp::Call(..)))
+        .unwrap();
+    let (args, ret) = types.calls.get_id(*call).unwrap();
+    assert_eq!(
+        args.iter().map(|t| t.node.clone()).collect::<Vec<_>>(),
+        [Ty::TInt, Ty::TInt]
+    );
+    assert!(matches!(&ret.node, RetTy::RetVal(t) if t.node == Ty::TInt));
+
+    let (scall, _) = nodes
+        .stmts
+        .iter()
+        .find(|(_, s)| matches!(s, Stmt::SCall(..)))
+        .unwrap();
+    let (args, ret) = types.calls.get_id(*scall).unwrap();
+    assert_eq!(
+        args.iter().map(|t| t.node.clone()).collect::<Vec<_>>(),
+        [Ty::TBool]
+    );
+    assert_eq!(ret.node, RetTy::RetVoid);
+    assert_eq!(types.calls.len(), 2);
+}
+
+// the source text of every error reported for `src`, in order
+fn error_texts(src: &str) -> Vec<&str> {
+    match typecheck_prog_all(&parse(src)) {
+        Ok(_) => vec![],
+        Err(errs) => errs
+            .iter()
+            .map(|e| &src[e.span.start..e.span.end])
+            .collect(),
+    }
+}
+
+#[test]
+fn test_all_errors_reported_in_order() {
+    let src = "int f() { var a = 1 + true; return a; }
+        void g(int x) { x = false; if (x) { return; } return; }
+        global s = 3 < \"no\";";
+    assert_eq!(error_texts(src), ["true", "x = false;", "x", "\"no\""]);
+
+    // the single-error entry point reports the first of them
+    let first = typecheck_prog(&parse(src)).unwrap_err();
+    assert_eq!(&src[first.span.start..first.span.end], "true");
+}
+
+#[test]
+fn test_errors_do_not_cascade() {
+    // x is poisoned by its initializer: none of its uses are reported
+    let src = "void show(int i) { return; }
+        void f() {
+            var x = nope + 1;
+            var y = x * 2;
+            x = 3;
+            show(x);
+            return;
+        }";
+    assert_eq!(error_texts(src), ["nope"]);
+
+    // an error inside an argument is reported once, and the other
+    // arguments are still checked
+    let src = "int add(int a, int b) { return a + b; }
+        int f() { return add(-true, !1); }";
+    assert_eq!(error_texts(src), ["true", "1"]);
+
+    // a declared type survives an ill-typed initializer
+    let src = "int f() { int x = true; return x + 1; }";
+    assert_eq!(error_texts(src), ["int x = true;"]);
+}
+
+#[test]
+fn test_checking_continues_past_errors() {
+    // bodies are checked even when the guard is wrong, and break
+    // after the loop is still outside of it
+    let src = "void f() {
+            while (1) { return 2; }
+            break;
+            for (var i = 0; i; i++) { i = true; }
+            var i = false;
+            return;
+        }";
+    assert_eq!(
+        error_texts(src),
+        ["1", "return 2;", "break;", "i", "i = true;"]
+    );
+
+    // a return with an error still counts as returning
+    assert_eq!(error_texts("int f() { return true; }"), ["return true;"]);
+
+    // both sides of an assignment are checked, even when one fails
+    assert_eq!(
+        error_texts("void f() { var x = nope; x = alsonope; return; }"),
+        ["nope", "alsonope"]
+    );
+    assert_eq!(
+        error_texts("void f() { bool b = true; b += nope; return; }"),
+        ["nope"]
+    );
+    assert_eq!(
+        error_texts("int g() { return 1; } void f() { g() = nope; return; }"),
+        ["g()", "nope"]
+    );
+}
+
+#[test]
+fn test_error_kinds() {
+    use ::common::TypeErrorKind as K;
+    // a program with one error, and a test for its kind
+    type Case = (&'static str, fn(&K) -> bool);
+    let cases: &[Case] = &[
+        ("int f(int a) { return f(1, 2); }", |k| {
+            matches!(
+                k,
+                K::ArityMismatch {
+                    expected: 1,
+                    found: 2
+                }
+            )
+        }),
+        ("void g() { return; } void f() { g(0); return; }", |k| {
+            matches!(
+                k,
+                K::ArityMismatch {
+                    expected: 0,
+                    found: 1
+                }
+            )
+        }),
+        ("int f(int a) { return a[0]; }", |k| {
+            matches!(k, K::NotIndexable { .. })
+        }),
+        ("int f(bool b) { return length(b); }", |k| {
+            matches!(k, K::NotIndexable { .. })
+        }),
+        ("void f(int a) { for (x in a) { } return; }", |k| {
+            matches!(k, K::NotIndexable { .. })
+        }),
+        (
+            "int f(bool b) { if (b) { return 1; } }",
+            |k| matches!(k, K::MissingReturn { name } if name == "f"),
+        ),
+        (
+            "struct P { int x; bool x; }",
+            |k| matches!(k, K::DuplicateField { struct_name, field } if struct_name == "P" && field == "x"),
+        ),
+        (
+            "struct P { int x; } void f() { var p = new P { x = 1; x = 2 }; return; }",
+            |k| matches!(k, K::DuplicateField { field, .. } if field == "x"),
+        ),
+        (
+            "struct P { int x; } int f(P p) { return p.y; }",
+            |k| matches!(k, K::UnknownField { struct_name, field } if struct_name == "P" && field == "y"),
+        ),
+        (
+            "struct P { int x; } void f() { var p = new P { x = 1; y = 2 }; return; }",
+            |k| matches!(k, K::UnknownField { field, .. } if field == "y"),
+        ),
+        (
+            "void f() { var q = new Q { }; return; }",
+            |k| matches!(k, K::UnknownStruct { name } if name == "Q"),
+        ),
+        ("int g() { return 1; } void f() { g() = 2; return; }", |k| {
+            matches!(k, K::InvalidLvalue)
+        }),
+        ("int g() { return 1; } void f() { g = g; return; }", |k| {
+            matches!(k, K::InvalidLvalue)
+        }),
+        (
+            "void f() { var x = 1; var x = 2; return; }",
+            |k| matches!(k, K::Redeclaration { name } if name == "x"),
+        ),
+        ("void g() { return; } int f() { return g(); }", |k| {
+            matches!(k, K::VoidInExpression)
+        }),
+        (
+            "struct P { int x; int y; } void f() { var p = new P { x = 1 }; return; }",
+            |k| matches!(k, K::MissingField { struct_name, field } if struct_name == "P" && field == "y"),
+        ),
+        (
+            "void f() { break; }",
+            |k| matches!(k, K::NotInLoop { stmt } if stmt == "break"),
+        ),
+        (
+            "void f() { while (true) { return; } continue; }",
+            |k| matches!(k, K::NotInLoop { stmt } if stmt == "continue"),
+        ),
+        ("void f() { string s; return; }", |k| {
+            matches!(k, K::NoDefaultValue { .. })
+        }),
+        ("void f() { var a = new string[3]; return; }", |k| {
+            matches!(k, K::NoDefaultValue { .. })
+        }),
+    ];
+    for (src, is_kind) in cases {
+        let err = typecheck_prog(&parse(src)).unwrap_err();
+        assert!(is_kind(&err.kind), "`{}`: {:?}", src, err.kind);
+    }
+}
@@ -2,7 +2,7 @@
 
 use std::fmt;
 
-pub type Lbl = String;
+pub type Lbl = common::Symbol;
 pub type Quad = i64;
 
 #[derive(Debug, Clone)]
@@ -64,6 +64,8 @@ pub enum Opcode {
     Addq,
     Subq,
     Imulq,
+    Cqto,  // sign-extend %rax into %rdx:%rax
+    Idivq, // %rdx:%rax / src → quotient %rax, remainder %rdx
     Xorq,
     Orq,
     Andq,
@@ -126,7 +128,7 @@ pub mod asm {
     ///
     /// Example: `lbl("main")` → `Operand::Imm(Imm::Lbl("main"))`
     pub fn lbl(s: &str) -> Operand {
-        Operand::Imm(Imm::Lbl(s.to_string()))
+        Operand::Imm(Imm::Lbl(s.into()))
     }
 
     /// Register reference → Reg
@@ -141,7 +143,7 @@ pub mod asm {
     /// Example: `data("str", vec![Data::Asciz("hello".to_string())])`
     pub fn data(label: &str, ds: Vec<Data>) -> Elem {
         Elem {
-            lbl: label.to_string(),
+            lbl: label.into(),
             global: true,
             asm: Asm::Data(ds),
         }
@@ -152,7 +154,7 @@ pub mod asm {
     /// Example: `text("loop", vec![...])`
     pub fn text(label: &str, is: Vec<Ins>) -> Elem {
         Elem {
-            lbl: label.to_string(),
+            lbl: label.into(),
             global: false,
             asm: Asm::Text(is),
         }
@@ -163,7 +165,7 @@ pub mod asm {
     /// Example: `gtext("main", vec![...])`
     pub fn gtext(label: &str, is: Vec<Ins>) -> Elem {
         Elem {
-            lbl: label.to_string(),
+            lbl: label.into(),
             global: true,
             asm: Asm::Text(is),
         }
@@ -248,6 +250,8 @@ impl fmt::Display for Opcode {
             Opcode::Addq => write!(f, "addq"),
             Opcode::Subq => write!(f, "subq"),
             Opcode::Imulq => write!(f, "imulq"),
+            Opcode::Cqto => write!(f, "cqto"),
+            Opcode::Idivq => write!(f, "idivq"),
             Opcode::Xorq => write!(f, "xorq"),
             Opcode::Orq => write!(f, "orq"),
             Opcode::Andq => write!(f, "andq"),